@@ -0,0 +1,172 @@
+#![no_std]
+
+#[cfg(feature = "alloc")]
+extern crate alloc;
+#[cfg(test)]
+extern crate std;
+
+use core::fmt::Write;
+
+use cordoba::{CDBAccess, IterState, LookupState, ReadError, Reader};
+
+pub fn get<'c>(cdb: &'c Reader<&[u8]>, key: &[u8]) -> Result<Option<&'c [u8]>, ReadError> {
+    cdb.get(key)
+}
+
+pub fn count_values<A: CDBAccess>(cdb: &Reader<A>, key: &[u8]) -> Result<usize, ReadError> {
+    let mut state = LookupState::new(cdb, key);
+    let mut count = 0;
+
+    while let Some(v) = state.next(cdb, key) {
+        v?;
+        count += 1;
+    }
+    Ok(count)
+}
+
+pub fn count_records<A: CDBAccess>(cdb: &Reader<A>) -> Result<usize, ReadError> {
+    let mut state = IterState::default();
+    let mut count = 0;
+
+    while let Some(kv) = state.next(cdb) {
+        kv?;
+        count += 1;
+    }
+    Ok(count)
+}
+
+#[cfg(feature = "alloc")]
+pub fn build(records: &[(&[u8], &[u8])]) -> Result<alloc::vec::Vec<u8>, cordoba::WriteError> {
+    let mut writer = cordoba::MemWriter::new(alloc::vec::Vec::new())?;
+
+    for (k, v) in records {
+        writer.write(k, v)?;
+    }
+    writer.finish()
+}
+
+struct SliceWriter<'b> {
+    buf: &'b mut [u8],
+    len: usize,
+}
+
+impl<'b> Write for SliceWriter<'b> {
+    fn write_str(&mut self, s: &str) -> core::fmt::Result {
+        let end = self.len + s.len();
+        self.buf
+            .get_mut(self.len..end)
+            .ok_or(core::fmt::Error)?
+            .copy_from_slice(s.as_bytes());
+        self.len = end;
+        Ok(())
+    }
+}
+
+pub fn describe<'b>(error: &ReadError, buf: &'b mut [u8]) -> Result<&'b str, core::fmt::Error> {
+    let mut w = SliceWriter { buf, len: 0 };
+    write!(w, "{}", error)?;
+    let len = w.len;
+    core::str::from_utf8(&buf[..len]).map_err(|_| core::fmt::Error)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::vec::Vec;
+
+    fn hash(d: &[u8]) -> u32 {
+        d.iter()
+            .fold(5381u32, |h, &c| (h << 5).wrapping_add(h) ^ u32::from(c))
+    }
+
+    fn build(records: &[(&[u8], &[u8])]) -> Vec<u8> {
+        let mut out = std::vec![0u8; 2048];
+        let mut positions = Vec::new();
+
+        for (k, v) in records {
+            positions.push((hash(k), out.len() as u32));
+            out.extend_from_slice(&(k.len() as u32).to_le_bytes());
+            out.extend_from_slice(&(v.len() as u32).to_le_bytes());
+            out.extend_from_slice(k);
+            out.extend_from_slice(v);
+        }
+
+        for t in 0..256 {
+            let entries: Vec<_> = positions
+                .iter()
+                .filter(|(h, _)| *h as usize % 256 == t)
+                .collect();
+            let tlen = entries.len() * 2;
+            let mut slots = std::vec![(0u32, 0u32); tlen];
+
+            for &&(h, pos) in &entries {
+                let mut slot = (h as usize >> 8) % tlen;
+                while slots[slot].1 != 0 {
+                    slot = (slot + 1) % tlen;
+                }
+                slots[slot] = (h, pos);
+            }
+
+            let tpos = out.len() as u32;
+            out[t * 8..t * 8 + 4].copy_from_slice(&tpos.to_le_bytes());
+            out[t * 8 + 4..t * 8 + 8].copy_from_slice(&(tlen as u32).to_le_bytes());
+            for (h, pos) in slots {
+                out.extend_from_slice(&h.to_le_bytes());
+                out.extend_from_slice(&pos.to_le_bytes());
+            }
+        }
+
+        out
+    }
+
+    #[test]
+    fn read_without_std() {
+        let data = build(&[(b"one", b"1"), (b"two", b"2"), (b"one", b"uno")]);
+        let cdb = Reader::new(&data[..]).unwrap();
+
+        assert_eq!(get(&cdb, b"one").unwrap(), Some(&b"1"[..]));
+        assert_eq!(get(&cdb, b"two").unwrap(), Some(&b"2"[..]));
+        assert_eq!(get(&cdb, b"three").unwrap(), None);
+        assert_eq!(count_values(&cdb, b"one").unwrap(), 2);
+        assert_eq!(count_records(&cdb).unwrap(), 3);
+    }
+
+    #[cfg(feature = "alloc")]
+    #[test]
+    fn write_without_std() {
+        let records: &[(&[u8], &[u8])] = &[(b"one", b"1"), (b"two", b"2"), (b"one", b"uno")];
+        let data = super::build(records).unwrap();
+        let (tables, trailer) = data.split_at(data.len() - 8);
+        assert_eq!(tables, &build(records)[..]);
+        assert_eq!(trailer, b"cdbrhood");
+
+        let mut buf = [0u8; 2100];
+        let mut writer = cordoba::MemWriter::new(&mut buf[..]).unwrap();
+        writer.write(b"one", b"1").unwrap();
+        let data = writer.finish().unwrap();
+        let cdb = Reader::new(&*data).unwrap();
+        assert_eq!(get(&cdb, b"one").unwrap(), Some(&b"1"[..]));
+
+        let mut buf = [0u8; 2060];
+        let mut writer = cordoba::MemWriter::new(&mut buf[..]).unwrap();
+        assert_eq!(
+            writer.write(b"key", b"a rather long value"),
+            Err(cordoba::WriteError::CapacityExceeded {
+                needed: 2078,
+                capacity: 2060
+            })
+        );
+    }
+
+    #[test]
+    fn display_without_std() {
+        let data = [0u8; 2048];
+        let err = Reader::new(&data[..]).unwrap_err();
+        let mut buf = [0u8; 128];
+
+        assert_eq!(
+            describe(&err, &mut buf).unwrap(),
+            "all header entries are empty"
+        );
+    }
+}
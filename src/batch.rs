@@ -0,0 +1,109 @@
+use alloc::vec::Vec;
+
+use super::*;
+
+const PREFETCH_SLOTS: usize = 8;
+const RECORD_PREFETCH: usize = 64;
+
+fn prefetch_ranges<A: CDBAccess>(access: &A, ranges: &mut [(usize, usize)]) {
+    ranges.sort_unstable();
+
+    let mut iter = ranges.iter().copied().filter(|&(pos, end)| pos < end);
+    let mut cur = match iter.next() {
+        Some(r) => r,
+        None => return,
+    };
+
+    for (pos, end) in iter {
+        if pos <= cur.1 {
+            cur.1 = cur.1.max(end);
+        } else {
+            access.prefetch(cur.0, cur.1 - cur.0);
+            cur = (pos, end);
+        }
+    }
+    access.prefetch(cur.0, cur.1 - cur.0);
+}
+
+struct Probe {
+    hash: Hash,
+    table: PosLen,
+    home: usize,
+}
+
+impl<A: CDBAccess, L: Layout> Reader<A, L> {
+    fn prepare_batch(&self, keys: &[&[u8]]) -> Vec<Hash> {
+        let probes: Vec<Probe> = keys
+            .iter()
+            .map(|key| {
+                let hash = self.layout().hash(key);
+                let table = self.tables()[L::table_of(hash)];
+                let home = if table.len != 0 {
+                    L::slot_of(hash, table.len)
+                } else {
+                    0
+                };
+                Probe { hash, table, home }
+            })
+            .collect();
+
+        let mut ranges: Vec<(usize, usize)> = probes
+            .iter()
+            .filter(|p| p.table.len != 0)
+            .map(|p| {
+                let start = p.table.pos + p.home * L::PAIR_SIZE;
+                let slots = PREFETCH_SLOTS.min(p.table.len - p.home);
+                (start, start + slots * L::PAIR_SIZE)
+            })
+            .collect();
+        prefetch_ranges(self.access(), &mut ranges);
+
+        let mut order: Vec<usize> = (0..probes.len()).collect();
+        order.sort_unstable_by_key(|&i| (probes[i].table.pos, probes[i].home));
+
+        ranges.clear();
+        for i in order {
+            let (probe, key) = (&probes[i], keys[i]);
+            let len = probe.table.len;
+            for i in 0..len {
+                let slot = (probe.home + i) % len;
+                let (hash, ptr) = match self.read_hash_pos(probe.table.pos + slot * L::PAIR_SIZE) {
+                    Ok(v) => v,
+                    Err(_) => break,
+                };
+                if ptr == 0 {
+                    break;
+                }
+                // Only a hint: a pointer outside the records is left for
+                // the lookup itself to report.
+                if ptr < L::HEADER_SIZE || ptr >= self.records_end() {
+                    break;
+                }
+                if hash == probe.hash {
+                    let end = ptr.saturating_add(L::PAIR_SIZE + key.len() + RECORD_PREFETCH);
+                    ranges.push((ptr, end.min(self.access().len())));
+                    break;
+                }
+            }
+        }
+        prefetch_ranges(self.access(), &mut ranges);
+
+        probes.iter().map(|p| p.hash).collect()
+    }
+
+    pub fn get_many<'c>(&'c self, keys: &[&[u8]]) -> Vec<Result<Option<A::Data<'c>>, ReadError>> {
+        let hashes = self.prepare_batch(keys);
+        keys.iter()
+            .zip(hashes)
+            .map(|(key, hash)| self.get_hashed(hash, key))
+            .collect()
+    }
+
+    pub fn lookup_many<'c>(&'c self, keys: &[&[u8]]) -> Vec<Result<Vec<A::Data<'c>>, ReadError>> {
+        let hashes = self.prepare_batch(keys);
+        keys.iter()
+            .zip(hashes)
+            .map(|(key, hash)| self.lookup_hashed(hash, key).collect())
+            .collect()
+    }
+}
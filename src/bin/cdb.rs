@@ -1,19 +1,31 @@
-use std::fs::File;
 use std::io::Write;
 
-use cordoba::Reader;
-use memmap::Mmap;
+use cordoba::{Cdb64, Classic, Keyed, Layout, MapOptions, MappedFile, Reader};
 
 use clap::{App, Arg, ArgMatches, SubCommand};
 
-fn cdb_open(fname: &str) -> std::io::Result<Reader<Mmap>> {
-    let f = File::open(fname)?;
-    let map = unsafe { Mmap::map(&f) }?;
-    Ok(Reader::new(map)?)
+fn cdb_open<L: Layout>(map: MappedFile) -> std::io::Result<Reader<MappedFile, L>> {
+    Ok(Reader::with_layout(map)?)
 }
 
-fn cmd_query(matches: &ArgMatches) -> std::io::Result<()> {
-    let reader = cdb_open(matches.value_of("cdbfile").unwrap())?;
+macro_rules! cdb_run {
+    ($cmd:ident, $matches:expr) => {{
+        let matches = $matches;
+        let map = MappedFile::open(matches.value_of("cdbfile").unwrap(), &MapOptions::new())?;
+        if Cdb64::detect(map.as_ref()) {
+            $cmd(&cdb_open::<Cdb64>(map)?, matches)
+        } else if Keyed::detect(map.as_ref()) {
+            $cmd(&cdb_open::<Keyed>(map)?, matches)
+        } else {
+            $cmd(&cdb_open::<Classic>(map)?, matches)
+        }
+    }};
+}
+
+fn cmd_query<L: Layout>(
+    reader: &Reader<MappedFile, L>,
+    matches: &ArgMatches<'_>,
+) -> std::io::Result<()> {
     let key = matches.value_of("key").unwrap().as_bytes();
     let recno = matches.value_of("recno");
     let stdout = std::io::stdout();
@@ -27,7 +39,7 @@ fn cmd_query(matches: &ArgMatches) -> std::io::Result<()> {
 
         if let Some(v) = reader.lookup(key).nth(recno - 1) {
             let v = v?;
-            handle.write_all(&v)?;
+            handle.write_all(v)?;
             handle.write_all(b"\n")?;
             return Ok(());
         }
@@ -36,30 +48,86 @@ fn cmd_query(matches: &ArgMatches) -> std::io::Result<()> {
 
     for v in reader.lookup(key) {
         let v = v?;
-        handle.write_all(&v)?;
+        handle.write_all(v)?;
         handle.write_all(b"\n")?;
     }
 
     Ok(())
 }
 
-fn cmd_dump(matches: &ArgMatches) -> std::io::Result<()> {
-    let reader = cdb_open(matches.value_of("cdbfile").unwrap())?;
-
+fn cmd_dump<L: Layout>(reader: &Reader<MappedFile, L>, _: &ArgMatches<'_>) -> std::io::Result<()> {
     let stdout = std::io::stdout();
     let mut handle = stdout.lock();
 
-    for res in &reader {
+    for res in reader {
         let (k, v) = res?;
-        handle.write_all(&k)?;
+        handle.write_all(k)?;
         handle.write_all(b" = ")?;
-        handle.write_all(&v)?;
+        handle.write_all(v)?;
         handle.write_all(b"\n")?;
     }
 
     Ok(())
 }
 
+fn cmd_stats<L: Layout>(reader: &Reader<MappedFile, L>, _: &ArgMatches<'_>) -> std::io::Result<()> {
+    let stats = reader.stats()?;
+
+    let stdout = std::io::stdout();
+    let mut handle = stdout.lock();
+    let cnt = stats.records;
+    let percent = |n: usize| (n * 100).checked_div(cnt).unwrap_or(0);
+
+    let tables: Vec<_> = stats.tables.iter().filter(|t| t.slots != 0).collect();
+    let tmin = tables.iter().map(|t| t.slots).min().unwrap_or(0);
+    let tmax = tables.iter().map(|t| t.slots).max().unwrap_or(0);
+    let tavg = if tables.is_empty() {
+        0
+    } else {
+        stats.slots() / tables.len()
+    };
+    let collisions = cnt.saturating_sub(stats.distances.first().copied().unwrap_or(0));
+
+    writeln!(handle, "number of records: {}", cnt)?;
+    writeln!(handle, "distinct keys: {}", stats.distinct_keys)?;
+    writeln!(
+        handle,
+        "key min/avg/max length: {}/{}/{}",
+        stats.key_len.min,
+        stats.key_len.avg(cnt) as u64,
+        stats.key_len.max
+    )?;
+    writeln!(
+        handle,
+        "val min/avg/max length: {}/{}/{}",
+        stats.value_len.min,
+        stats.value_len.avg(cnt) as u64,
+        stats.value_len.max
+    )?;
+    writeln!(
+        handle,
+        "hash tables/entries/collisions: {}/{}/{}",
+        tables.len(),
+        stats.slots(),
+        collisions
+    )?;
+    writeln!(
+        handle,
+        "hash table min/avg/max length: {}/{}/{}",
+        tmin, tavg, tmax
+    )?;
+    writeln!(handle, "hash table load factor: {:.2}", stats.load_factor())?;
+    writeln!(handle, "hash table distances:")?;
+    for i in 0..10 {
+        let n = stats.distances.get(i).copied().unwrap_or(0);
+        writeln!(handle, " d{}: {:6} {:2}%", i, n, percent(n))?;
+    }
+    let rest: usize = stats.distances.iter().skip(10).sum();
+    writeln!(handle, ">9: {:6} {:2}%", rest, percent(rest))?;
+
+    Ok(())
+}
+
 fn main() -> std::io::Result<()> {
     let newline_arg = Arg::with_name("newline").short("m");
     let cdbfile_arg = Arg::with_name("cdbfile").index(1).required(true);
@@ -79,12 +147,19 @@ fn main() -> std::io::Result<()> {
                 .arg(newline_arg.clone())
                 .arg(cdbfile_arg.clone()),
         )
+        .subcommand(
+            SubCommand::with_name("-s")
+                .about("statistics")
+                .arg(cdbfile_arg.clone()),
+        )
         .get_matches();
 
     if let Some(matches) = matches.subcommand_matches("-q") {
-        cmd_query(matches)?;
+        cdb_run!(cmd_query, matches)?;
     } else if let Some(matches) = matches.subcommand_matches("-d") {
-        cmd_dump(matches)?;
+        cdb_run!(cmd_dump, matches)?;
+    } else if let Some(matches) = matches.subcommand_matches("-s") {
+        cdb_run!(cmd_stats, matches)?;
     }
 
     Ok(())
@@ -0,0 +1,301 @@
+use core::convert::TryInto;
+
+use super::siphash::siphash24;
+use super::*;
+
+pub(crate) const MAX_PAIR_SIZE: usize = 16;
+
+// Header bytes handed to `from_header`: enough to recognize any known
+// layout, so that one layout can refuse a file written in another.
+pub(crate) fn header_len<L: Layout>(datalen: usize) -> usize {
+    L::HEADER_SIZE.max(datalen.min(<Cdb64 as Layout>::HEADER_SIZE))
+}
+
+pub trait Layout: Clone {
+    const ENTRIES: usize;
+    const WORD_SIZE: usize;
+    const PAIR_SIZE: usize = 2 * Self::WORD_SIZE;
+    const HEADER_SIZE: usize = Self::ENTRIES * Self::PAIR_SIZE;
+    const MAX_OFFSET: u64;
+
+    type Tables: AsRef<[PosLen]> + AsMut<[PosLen]> + Clone;
+
+    fn empty_tables() -> Self::Tables;
+
+    fn from_header(header: &[u8], datalen: usize) -> Result<Self, ReadError>;
+
+    fn write_header(&self, _extra: &mut [u8]) {}
+
+    fn hash(&self, key: &[u8]) -> Hash;
+
+    #[inline]
+    fn table_of(hash: Hash) -> usize {
+        hash.0 as usize % Self::ENTRIES
+    }
+
+    #[inline]
+    fn slot_of(hash: Hash, tlen: usize) -> usize {
+        (hash.0 as usize >> 8) % tlen
+    }
+
+    fn read_word(data: &[u8]) -> u64;
+
+    fn write_word(value: u64, out: &mut [u8]);
+}
+
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct Classic;
+
+impl Layout for Classic {
+    const ENTRIES: usize = ENTRIES;
+    const WORD_SIZE: usize = 4;
+    const MAX_OFFSET: u64 = u32::MAX as u64;
+
+    type Tables = [PosLen; ENTRIES];
+
+    fn empty_tables() -> Self::Tables {
+        [PosLen::default(); ENTRIES]
+    }
+
+    fn from_header(header: &[u8], datalen: usize) -> Result<Self, ReadError> {
+        // A Keyed magic read as a record header would announce a key of
+        // nearly 2 GB, so it never shows up in a Classic file.
+        if Cdb64::detect_header(header, datalen) || Keyed::detect(header) {
+            return Err(ReadError::BadMagic);
+        }
+        Ok(Classic)
+    }
+
+    #[inline]
+    fn hash(&self, key: &[u8]) -> Hash {
+        Hash::new(key)
+    }
+
+    #[inline]
+    fn read_word(data: &[u8]) -> u64 {
+        u32::from_le_bytes(data.try_into().unwrap()).into()
+    }
+
+    #[inline]
+    fn write_word(value: u64, out: &mut [u8]) {
+        debug_assert!(value <= Self::MAX_OFFSET);
+        out.copy_from_slice(&(value as u32).to_le_bytes());
+    }
+}
+
+const CDB64_MAGIC: &[u8; 8] = b"cdb64\0\0\0";
+
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct Cdb64;
+
+impl Cdb64 {
+    pub fn detect(data: &[u8]) -> bool {
+        Self::detect_header(data, data.len())
+    }
+
+    // The magic sits where a Classic file keeps record data, so it could
+    // come from a key. Every table must also fit in the file: read as
+    // 64-bit words, a Classic directory yields lengths built from Classic
+    // offsets, which always reach past the end of the file.
+    fn detect_header(header: &[u8], datalen: usize) -> bool {
+        let start = Self::ENTRIES * Self::PAIR_SIZE;
+        if header.get(start..Self::HEADER_SIZE) != Some(&CDB64_MAGIC[..]) {
+            return false;
+        }
+        header[..start].chunks_exact(Self::PAIR_SIZE).all(|pair| {
+            let (pos, len) = pair.split_at(Self::WORD_SIZE);
+            let (pos, len) = (Self::read_word(pos), Self::read_word(len));
+            len.checked_mul(Self::PAIR_SIZE as u64)
+                .and_then(|sz| sz.checked_add(pos))
+                .is_some_and(|end| pos >= Self::HEADER_SIZE as u64 && end <= datalen as u64)
+        })
+    }
+}
+
+impl Layout for Cdb64 {
+    const ENTRIES: usize = ENTRIES;
+    const WORD_SIZE: usize = 8;
+    const HEADER_SIZE: usize = ENTRIES * 16 + 8;
+    const MAX_OFFSET: u64 = u64::MAX;
+
+    type Tables = [PosLen; ENTRIES];
+
+    fn empty_tables() -> Self::Tables {
+        [PosLen::default(); ENTRIES]
+    }
+
+    fn from_header(header: &[u8], _datalen: usize) -> Result<Self, ReadError> {
+        let start = Self::ENTRIES * Self::PAIR_SIZE;
+        if header[start..Self::HEADER_SIZE] == CDB64_MAGIC[..] {
+            Ok(Cdb64)
+        } else {
+            Err(ReadError::BadMagic)
+        }
+    }
+
+    fn write_header(&self, extra: &mut [u8]) {
+        extra.copy_from_slice(CDB64_MAGIC);
+    }
+
+    #[inline]
+    fn hash(&self, key: &[u8]) -> Hash {
+        Hash::new(key)
+    }
+
+    #[inline]
+    fn read_word(data: &[u8]) -> u64 {
+        u64::from_le_bytes(data.try_into().unwrap())
+    }
+
+    #[inline]
+    fn write_word(value: u64, out: &mut [u8]) {
+        out.copy_from_slice(&value.to_le_bytes());
+    }
+}
+
+const KEYED_MAGIC: &[u8; 8] = b"cdbsip\0\0";
+
+#[derive(Clone, Copy, PartialEq, Eq)]
+pub struct Keyed {
+    k0: u64,
+    k1: u64,
+}
+
+impl Keyed {
+    pub fn new(seed: [u8; 16]) -> Self {
+        Keyed {
+            k0: u64::from_le_bytes(seed[..8].try_into().unwrap()),
+            k1: u64::from_le_bytes(seed[8..].try_into().unwrap()),
+        }
+    }
+
+    #[cfg(feature = "std")]
+    pub fn random() -> Self {
+        use std::collections::hash_map::RandomState;
+        use std::hash::{BuildHasher, Hasher};
+
+        // RandomState is seeded from the OS; hashing through it yields
+        // unpredictable words without a dependency on a rand crate.
+        let state = RandomState::new();
+        let word = |n: u64| {
+            let mut hasher = state.build_hasher();
+            hasher.write_u64(n);
+            hasher.finish()
+        };
+        Keyed {
+            k0: word(0),
+            k1: word(1),
+        }
+    }
+
+    pub fn seed(&self) -> [u8; 16] {
+        let mut seed = [0u8; 16];
+        seed[..8].copy_from_slice(&self.k0.to_le_bytes());
+        seed[8..].copy_from_slice(&self.k1.to_le_bytes());
+        seed
+    }
+
+    pub fn detect(data: &[u8]) -> bool {
+        let start = Self::ENTRIES * Self::PAIR_SIZE;
+        data.get(start..start + KEYED_MAGIC.len()) == Some(&KEYED_MAGIC[..])
+    }
+}
+
+impl core::fmt::Debug for Keyed {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        write!(f, "Keyed {{ .. }}")
+    }
+}
+
+impl Layout for Keyed {
+    const ENTRIES: usize = ENTRIES;
+    const WORD_SIZE: usize = 4;
+    const HEADER_SIZE: usize = ENTRIES * 8 + 8 + 16;
+    const MAX_OFFSET: u64 = u32::MAX as u64;
+
+    type Tables = [PosLen; ENTRIES];
+
+    fn empty_tables() -> Self::Tables {
+        [PosLen::default(); ENTRIES]
+    }
+
+    fn from_header(header: &[u8], _datalen: usize) -> Result<Self, ReadError> {
+        if !Self::detect(header) {
+            return Err(ReadError::BadMagic);
+        }
+        let seed = &header[Self::HEADER_SIZE - 16..Self::HEADER_SIZE];
+        Ok(Keyed::new(seed.try_into().unwrap()))
+    }
+
+    fn write_header(&self, extra: &mut [u8]) {
+        extra[..8].copy_from_slice(KEYED_MAGIC);
+        extra[8..].copy_from_slice(&self.seed());
+    }
+
+    #[inline]
+    fn hash(&self, key: &[u8]) -> Hash {
+        Hash(siphash24(self.k0, self.k1, key) as u32)
+    }
+
+    #[inline]
+    fn read_word(data: &[u8]) -> u64 {
+        Classic::read_word(data)
+    }
+
+    #[inline]
+    fn write_word(value: u64, out: &mut [u8]) {
+        Classic::write_word(value, out)
+    }
+}
+
+#[cfg(all(test, feature = "alloc"))]
+mod tests {
+    use super::*;
+    use crate::read::tests::{check, fill};
+    use crate::MemWriter;
+    use alloc::vec::Vec;
+
+    #[test]
+    fn cdb64() {
+        let mut writer = MemWriter::with_layout(Vec::new(), Cdb64).unwrap();
+        fill(&mut writer, true);
+        let data = writer.finish().unwrap();
+
+        assert!(Cdb64::detect(&data));
+        assert!(matches!(Reader::new(&data[..]), Err(ReadError::BadMagic)));
+        check::<Cdb64>(&data);
+    }
+
+    #[test]
+    fn classic_holding_cdb64_magic() {
+        // The first record's value lands where Cdb64 keeps its magic.
+        let mut writer = MemWriter::new(Vec::new()).unwrap();
+        let mut value = alloc::vec![0u8; 4096 - 2048 - 8 - 3];
+        value.extend_from_slice(CDB64_MAGIC);
+        writer.write(b"key", &value).unwrap();
+        let data = writer.finish().unwrap();
+
+        assert_eq!(&data[4096..4104], CDB64_MAGIC);
+        assert!(!Cdb64::detect(&data));
+        let cdb = Reader::new(&data[..]).unwrap();
+        assert_eq!(cdb.get(b"key").unwrap(), Some(&value[..]));
+    }
+
+    #[test]
+    fn keyed() {
+        let mut writer = MemWriter::with_layout(Vec::new(), Keyed::new([7; 16])).unwrap();
+        fill(&mut writer, true);
+        let data = writer.finish().unwrap();
+
+        assert!(Keyed::detect(&data));
+        assert!(matches!(Reader::new(&data[..]), Err(ReadError::BadMagic)));
+        check::<Keyed>(&data);
+    }
+
+    #[test]
+    fn keyed_seed_changes_hash() {
+        let (a, b) = (Keyed::new([1; 16]), Keyed::new([2; 16]));
+        assert!(a.hash(b"key") != b.hash(b"key"));
+        assert!(a.hash(b"key") == Keyed::new([1; 16]).hash(b"key"));
+    }
+}
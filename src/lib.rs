@@ -1,12 +1,67 @@
 #![cfg_attr(not(feature = "std"), no_std)]
 #![warn(rust_2018_idioms)]
 
+#[cfg(feature = "alloc")]
+extern crate alloc;
+
+mod layout;
+mod perfect;
+mod pod;
 mod read;
+mod siphash;
 
+#[cfg(feature = "alloc")]
+mod batch;
+#[cfg(feature = "mmap")]
+mod mmap;
+#[cfg(feature = "alloc")]
+mod overlay;
+#[cfg(feature = "parallel")]
+mod parallel;
+#[cfg(all(feature = "std", unix))]
+mod pread;
+#[cfg(feature = "mmap")]
+mod reload;
 #[cfg(feature = "std")]
+mod seek;
+#[cfg(feature = "alloc")]
+mod shard;
+#[cfg(feature = "alloc")]
+mod shared;
+#[cfg(feature = "alloc")]
+mod stats;
+#[cfg(feature = "alloc")]
+mod verify;
+#[cfg(feature = "alloc")]
 mod write;
+
+pub use self::layout::{Cdb64, Classic, Keyed, Layout};
+#[cfg(feature = "mmap")]
+pub use self::mmap::{Advice, FileStatus, MapOptions, MappedFile};
+#[cfg(feature = "alloc")]
+pub use self::overlay::{OverlayIter, OverlayReader};
+pub use self::perfect::{PerfectIter, PerfectReader};
+pub use self::pod::{cast, cast_slice, Pod};
+#[cfg(all(feature = "std", unix))]
+pub use self::pread::{FileAccess, FileReader};
+#[cfg(feature = "mmap")]
+pub use self::reload::ReloadingReader;
+#[cfg(feature = "std")]
+pub use self::seek::{SeekAccess, SeekReader};
+#[cfg(feature = "std")]
+pub use self::shard::ShardedWriter;
+#[cfg(feature = "alloc")]
+pub use self::shard::{shard_of, ShardedReader};
+#[cfg(feature = "alloc")]
+pub use self::shared::{SharedIter, SharedLookup, SharedReader, Value};
+#[cfg(feature = "alloc")]
+pub use self::stats::{LengthStats, Stats, TableStats};
+#[cfg(feature = "alloc")]
+pub use self::verify::{VerifyProblem, VerifyReport};
 #[cfg(feature = "std")]
 pub use self::write::Writer;
+#[cfg(feature = "alloc")]
+pub use self::write::{Buffer, MemWriter, WriteError};
 
 #[cfg(feature = "python")]
 mod pymod;
@@ -14,17 +69,29 @@ mod pymod;
 pub use self::read::*;
 
 const ENTRIES: usize = 256;
-const PAIR_SIZE: usize = 8;
+const ROBINHOOD_MAGIC: &[u8; 8] = b"cdbrhood";
 
-#[derive(Copy, Clone)]
+#[derive(Copy, Clone, Debug, Default, PartialEq, Eq)]
 pub struct PosLen {
     pos: usize,
     len: usize,
 }
 
 impl PosLen {
-    fn valid(&self, datalen: usize) -> bool {
-        let data_sz = self.len.checked_mul(PAIR_SIZE);
+    pub fn pos(&self) -> usize {
+        self.pos
+    }
+
+    pub fn len(&self) -> usize {
+        self.len
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len == 0
+    }
+
+    fn valid(&self, datalen: usize, pair_size: usize) -> bool {
+        let data_sz = self.len.checked_mul(pair_size);
         let data_end = data_sz.and_then(|sz| sz.checked_add(self.pos));
 
         match data_end {
@@ -34,26 +101,18 @@ impl PosLen {
     }
 }
 
-#[derive(Copy, Clone, PartialEq)]
+#[derive(Copy, Clone, PartialEq, Eq)]
 pub struct Hash(pub u32);
 
 impl Hash {
-    fn new(d: &[u8]) -> Self {
+    // The djb hash; other layouts hash keys their own way, so callers go
+    // through `Layout::hash`.
+    pub(crate) fn new(d: &[u8]) -> Self {
         let h = d
             .iter()
             .fold(5381u32, |h, &c| (h << 5).wrapping_add(h) ^ u32::from(c));
         Hash(h)
     }
-
-    #[inline]
-    fn table(self) -> usize {
-        self.0 as usize % ENTRIES
-    }
-
-    #[inline]
-    fn slot(self, tlen: usize) -> usize {
-        (self.0 as usize >> 8) % tlen
-    }
 }
 
 impl core::fmt::Debug for Hash {
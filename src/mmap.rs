@@ -0,0 +1,211 @@
+use std::fs::{self, File};
+use std::io::{self, Read};
+use std::path::{Path, PathBuf};
+use std::time::SystemTime;
+
+use memmap2::{Mmap, MmapOptions};
+
+use super::*;
+
+const DEFAULT_READ_BELOW: u64 = 64 * 1024;
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Advice {
+    Normal,
+    Random,
+    Sequential,
+    WillNeed,
+}
+
+#[derive(Clone, Debug)]
+pub struct MapOptions {
+    populate: bool,
+    advice: Advice,
+    lock: bool,
+    read_below: u64,
+}
+
+impl Default for MapOptions {
+    fn default() -> Self {
+        MapOptions {
+            populate: false,
+            advice: Advice::Normal,
+            lock: false,
+            read_below: DEFAULT_READ_BELOW,
+        }
+    }
+}
+
+impl MapOptions {
+    pub fn new() -> Self {
+        Default::default()
+    }
+
+    pub fn populate(&mut self, populate: bool) -> &mut Self {
+        self.populate = populate;
+        self
+    }
+
+    pub fn advise(&mut self, advice: Advice) -> &mut Self {
+        self.advice = advice;
+        self
+    }
+
+    pub fn lock(&mut self, lock: bool) -> &mut Self {
+        self.lock = lock;
+        self
+    }
+
+    pub fn read_below(&mut self, len: u64) -> &mut Self {
+        self.read_below = len;
+        self
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum FileStatus {
+    Unchanged,
+    Modified,
+    Truncated,
+    Replaced,
+}
+
+enum Contents {
+    Mapped(Mmap),
+    Memory(Vec<u8>),
+}
+
+#[derive(PartialEq, Eq)]
+struct Identity {
+    len: u64,
+    modified: Option<SystemTime>,
+    #[cfg(unix)]
+    inode: (u64, u64),
+}
+
+impl Identity {
+    fn new(meta: &fs::Metadata) -> Self {
+        #[cfg(unix)]
+        use std::os::unix::fs::MetadataExt;
+
+        Identity {
+            len: meta.len(),
+            modified: meta.modified().ok(),
+            #[cfg(unix)]
+            inode: (meta.dev(), meta.ino()),
+        }
+    }
+}
+
+pub struct MappedFile {
+    contents: Contents,
+    file: File,
+    path: PathBuf,
+    identity: Identity,
+}
+
+impl std::fmt::Debug for MappedFile {
+    fn fmt(&self, fmt: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        fmt.debug_struct("MappedFile")
+            .field("path", &self.path)
+            .field("len", &self.identity.len)
+            .field("mapped", &self.is_mapped())
+            .finish()
+    }
+}
+
+impl MappedFile {
+    pub fn open<P: AsRef<Path>>(path: P, options: &MapOptions) -> io::Result<Self> {
+        let path = path.as_ref().to_path_buf();
+        let mut file = File::open(&path)?;
+        let identity = Identity::new(&file.metadata()?);
+
+        let contents = if identity.len < options.read_below {
+            let mut data = Vec::with_capacity(identity.len as usize);
+            file.read_to_end(&mut data)?;
+            Contents::Memory(data)
+        } else {
+            let mut mmap = MmapOptions::new();
+            if options.populate {
+                mmap.populate();
+            }
+            // The mapping stays valid while the file is replaced through a
+            // rename, but truncating the file underneath it makes reads
+            // fault. `status` reports both cases.
+            let map = unsafe { mmap.map(&file) }?;
+            Self::configure(&map, options)?;
+            Contents::Mapped(map)
+        };
+
+        Ok(MappedFile {
+            contents,
+            file,
+            path,
+            identity,
+        })
+    }
+
+    #[cfg(unix)]
+    fn configure(map: &Mmap, options: &MapOptions) -> io::Result<()> {
+        let advice = match options.advice {
+            Advice::Normal => memmap2::Advice::Normal,
+            Advice::Random => memmap2::Advice::Random,
+            Advice::Sequential => memmap2::Advice::Sequential,
+            Advice::WillNeed => memmap2::Advice::WillNeed,
+        };
+        map.advise(advice)?;
+        if options.lock {
+            map.lock()?;
+        }
+        Ok(())
+    }
+
+    #[cfg(not(unix))]
+    fn configure(_map: &Mmap, _options: &MapOptions) -> io::Result<()> {
+        Ok(())
+    }
+
+    pub fn status(&self) -> io::Result<FileStatus> {
+        let current = Identity::new(&self.file.metadata()?);
+        if current.len < self.identity.len {
+            return Ok(FileStatus::Truncated);
+        }
+        if current.len != self.identity.len || current.modified != self.identity.modified {
+            return Ok(FileStatus::Modified);
+        }
+
+        match fs::metadata(&self.path) {
+            Ok(meta) if Identity::new(&meta) == self.identity => Ok(FileStatus::Unchanged),
+            Ok(_) => Ok(FileStatus::Replaced),
+            Err(e) if e.kind() == io::ErrorKind::NotFound => Ok(FileStatus::Replaced),
+            Err(e) => Err(e),
+        }
+    }
+
+    pub fn is_mapped(&self) -> bool {
+        matches!(self.contents, Contents::Mapped(_))
+    }
+
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+
+    pub fn file(&self) -> &File {
+        &self.file
+    }
+}
+
+impl AsRef<[u8]> for MappedFile {
+    fn as_ref(&self) -> &[u8] {
+        match &self.contents {
+            Contents::Mapped(map) => map,
+            Contents::Memory(data) => data,
+        }
+    }
+}
+
+impl Reader<MappedFile> {
+    pub fn open<P: AsRef<Path>>(path: P, options: &MapOptions) -> Result<Self, ReadError> {
+        Reader::new(MappedFile::open(path, options)?)
+    }
+}
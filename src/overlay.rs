@@ -0,0 +1,176 @@
+use alloc::vec::Vec;
+
+use super::*;
+
+// Layers are stacked bottom to top: a key is resolved from the topmost
+// layer that has it, and a tombstone value hides it from the layers below.
+pub struct OverlayReader<A, L: Layout = Classic> {
+    layers: Vec<Reader<A, L>>,
+    tombstone: Option<Vec<u8>>,
+}
+
+impl<A, L: Layout> core::fmt::Debug for OverlayReader<A, L> {
+    fn fmt(&self, fmt: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        write!(fmt, "OverlayReader {{ layers: {} }}", self.layers.len())
+    }
+}
+
+impl<A, L: Layout> Default for OverlayReader<A, L> {
+    fn default() -> Self {
+        OverlayReader {
+            layers: Vec::new(),
+            tombstone: None,
+        }
+    }
+}
+
+impl<A, L: Layout> From<Vec<Reader<A, L>>> for OverlayReader<A, L> {
+    fn from(layers: Vec<Reader<A, L>>) -> Self {
+        OverlayReader {
+            layers,
+            tombstone: None,
+        }
+    }
+}
+
+impl<A, L: Layout> OverlayReader<A, L> {
+    pub fn new() -> Self {
+        Default::default()
+    }
+
+    pub fn push(&mut self, layer: Reader<A, L>) {
+        self.layers.push(layer);
+    }
+
+    pub fn pop(&mut self) -> Option<Reader<A, L>> {
+        self.layers.pop()
+    }
+
+    pub fn layers(&self) -> &[Reader<A, L>] {
+        &self.layers
+    }
+
+    pub fn set_tombstone(&mut self, tombstone: Option<&[u8]>) {
+        self.tombstone = tombstone.map(|t| t.to_vec());
+    }
+
+    pub fn tombstone(&self) -> Option<&[u8]> {
+        self.tombstone.as_deref()
+    }
+
+    fn is_tombstone(&self, value: &[u8]) -> bool {
+        self.tombstone.as_deref() == Some(value)
+    }
+}
+
+impl<A: CDBAccess, L: Layout> OverlayReader<A, L> {
+    // Returns the value along with the index of the layer it came from.
+    pub fn get_with_layer<'c>(
+        &'c self,
+        key: &[u8],
+    ) -> Result<Option<(usize, A::Data<'c>)>, ReadError> {
+        for (layer, cdb) in self.layers.iter().enumerate().rev() {
+            if let Some(value) = cdb.get(key)? {
+                if self.is_tombstone(value.as_ref()) {
+                    return Ok(None);
+                }
+                return Ok(Some((layer, value)));
+            }
+        }
+        Ok(None)
+    }
+
+    pub fn get<'c>(&'c self, key: &[u8]) -> Result<Option<A::Data<'c>>, ReadError> {
+        Ok(self.get_with_layer(key)?.map(|(_, v)| v))
+    }
+
+    // A record is visible when it is the one its own layer returns for the
+    // key and no layer above has the key at all.
+    fn is_visible(
+        &self,
+        layer: usize,
+        pos: usize,
+        key: &[u8],
+        value: &[u8],
+    ) -> Result<bool, ReadError> {
+        if self.is_tombstone(value) {
+            return Ok(false);
+        }
+        if self.layers[layer].get_with_offset(key)?.map(|(p, _)| p) != Some(pos) {
+            return Ok(false);
+        }
+        for cdb in &self.layers[layer + 1..] {
+            if cdb.get(key)?.is_some() {
+                return Ok(false);
+            }
+        }
+        Ok(true)
+    }
+
+    pub fn iter(&self) -> OverlayIter<'_, A, L> {
+        OverlayIter {
+            cdb: self,
+            layer: self.layers.len(),
+            state: IterState::default(),
+            empty: EmptyKeys::default(),
+        }
+    }
+}
+
+pub struct OverlayIter<'a, A, L: Layout = Classic> {
+    cdb: &'a OverlayReader<A, L>,
+    layer: usize,
+    state: IterState,
+    empty: EmptyKeys,
+}
+
+impl<'a, A, L: Layout> Clone for OverlayIter<'a, A, L> {
+    fn clone(&self) -> Self {
+        OverlayIter {
+            cdb: self.cdb,
+            layer: self.layer,
+            state: self.state,
+            empty: self.empty.clone(),
+        }
+    }
+}
+
+impl<'a, A: CDBAccess, L: Layout> Iterator for OverlayIter<'a, A, L> {
+    type Item = Result<(A::Data<'a>, A::Data<'a>), ReadError>;
+
+    // Walks the layers from the top down, each in file order.
+    fn next(&mut self) -> Option<Self::Item> {
+        while self.layer > 0 {
+            let layer = self.layer - 1;
+            let (cdb, empty) = (&self.cdb.layers[layer], &mut self.empty);
+            match self
+                .state
+                .next_unpadded(cdb, cdb.records_end(), |pos| empty.indexed(cdb, pos))
+            {
+                Some(Ok((pos, k, v))) => {
+                    match self.cdb.is_visible(layer, pos, k.as_ref(), v.as_ref()) {
+                        Ok(true) => return Some(Ok((k, v))),
+                        Ok(false) => (),
+                        Err(e) => return Some(Err(e)),
+                    }
+                }
+                Some(Err(e)) => return Some(Err(e)),
+                None => {
+                    self.layer = layer;
+                    self.state = IterState::default();
+                    self.empty = EmptyKeys::default();
+                }
+            }
+        }
+        None
+    }
+}
+
+impl<'a, A: CDBAccess, L: Layout> IntoIterator for &'a OverlayReader<A, L> {
+    type IntoIter = OverlayIter<'a, A, L>;
+    type Item = <OverlayIter<'a, A, L> as Iterator>::Item;
+
+    fn into_iter(self) -> OverlayIter<'a, A, L> {
+        self.iter()
+    }
+}
@@ -0,0 +1,90 @@
+use rayon::prelude::*;
+
+use super::*;
+
+// Enough chunks per thread to even out uneven record sizes.
+const CHUNKS_PER_THREAD: usize = 16;
+
+type KeyValue<'c, A> = (<A as CDBAccess>::Data<'c>, <A as CDBAccess>::Data<'c>);
+
+impl<A, L> Reader<A, L>
+where
+    A: CDBAccess,
+    L: Layout,
+    Self: Sync,
+{
+    // Every hash table slot points at the start of a record, so a sample
+    // of slot pointers splits the record region at record boundaries.
+    // Tables are read in order until enough pointers have been collected;
+    // each table's keys are spread over the whole file.
+    fn record_chunks(&self) -> Vec<(usize, usize)> {
+        let start = L::HEADER_SIZE;
+        let end = self.records_end();
+        let wanted = rayon::current_num_threads() * CHUNKS_PER_THREAD;
+
+        let mut bounds = Vec::new();
+        for table in 0..L::ENTRIES {
+            if bounds.len() >= wanted {
+                break;
+            }
+            let slots = match self.table_slots(table) {
+                Ok(slots) => slots,
+                Err(_) => break,
+            };
+            for slot in slots {
+                match slot {
+                    Ok((hash, ptr)) if ptr > start && ptr < end => {
+                        if self.is_boundary(table, hash, ptr) {
+                            bounds.push(ptr);
+                        }
+                    }
+                    Ok(_) => (),
+                    Err(_) => break,
+                }
+            }
+        }
+        bounds.sort_unstable();
+        bounds.dedup();
+
+        let step = bounds.len().div_ceil(wanted).max(1);
+        let mut chunks = Vec::with_capacity(wanted + 1);
+        let mut chunk_start = start;
+        for &bound in bounds.iter().skip(step - 1).step_by(step) {
+            chunks.push((chunk_start, bound));
+            chunk_start = bound;
+        }
+        chunks.push((chunk_start, end));
+        chunks
+    }
+
+    // A corrupt slot may point into the middle of a record, so only trust
+    // it when the key found there hashes to the slot's hash and table.
+    fn is_boundary(&self, table: usize, hash: Hash, ptr: usize) -> bool {
+        match self.get_key_and_value(ptr) {
+            Ok((k, _, _)) => {
+                let actual = self.layout().hash(k.as_ref());
+                actual == hash && L::table_of(actual) == table
+            }
+            Err(_) => false,
+        }
+    }
+
+    pub fn par_iter<'a>(
+        &'a self,
+    ) -> impl ParallelIterator<Item = Result<KeyValue<'a, A>, ReadError>> + 'a
+    where
+        A::Data<'a>: Send,
+    {
+        self.record_chunks()
+            .into_par_iter()
+            .flat_map_iter(move |(start, end)| {
+                let mut state = IterState(start);
+                let mut empty = EmptyKeys::default();
+                core::iter::from_fn(move || {
+                    state
+                        .next_unpadded(self, end, |pos| empty.indexed(self, pos))
+                        .map(|r| r.map(|(_, k, v)| (k, v)))
+                })
+            })
+    }
+}
@@ -0,0 +1,440 @@
+use core::convert::TryFrom;
+
+#[cfg(feature = "alloc")]
+use alloc::vec;
+#[cfg(feature = "alloc")]
+use alloc::vec::Vec;
+
+use super::layout::header_len;
+use super::siphash::siphash24;
+use super::*;
+
+pub(crate) const MAGIC: u64 = 0x4648_504d; // "MPHF"
+#[cfg(feature = "alloc")]
+const LAMBDA: usize = 3;
+// Displacement pairs tried for one bucket before moving on to the next seed.
+#[cfg(feature = "alloc")]
+const MAX_DISPLACEMENTS: usize = 1 << 14;
+
+#[inline]
+pub(crate) fn key_hash(key: &[u8]) -> u64 {
+    siphash24(0, 0, key)
+}
+
+#[inline]
+fn mix(mut z: u64) -> u64 {
+    z = (z ^ (z >> 30)).wrapping_mul(0xbf58_476d_1ce4_e5b9);
+    z = (z ^ (z >> 27)).wrapping_mul(0x94d0_49bb_1331_11eb);
+    z ^ (z >> 31)
+}
+
+#[derive(Clone, Copy)]
+struct KeyHashes {
+    bucket: u64,
+    f1: u64,
+    f2: u64,
+}
+
+impl KeyHashes {
+    #[inline]
+    fn new(seed: u64, hash: u64) -> Self {
+        let m = mix(hash ^ seed.wrapping_mul(0x9e37_79b9_7f4a_7c15));
+        KeyHashes {
+            bucket: m >> 32,
+            f1: m & 0xffff_ffff,
+            f2: mix(m) & 0xffff_ffff,
+        }
+    }
+
+    #[inline]
+    fn slot(self, d1: u64, d2: u64, slots: u64) -> u64 {
+        self.f1
+            .wrapping_mul(d1)
+            .wrapping_add(self.f2)
+            .wrapping_add(d2)
+            % slots
+    }
+}
+
+#[inline]
+fn check_hash(hash: u64) -> Hash {
+    Hash(hash as u32)
+}
+
+#[cfg(feature = "alloc")]
+pub(crate) struct Perfect {
+    pub seed: u64,
+    pub displacements: Vec<(u64, u64)>,
+    pub slots: Vec<(Hash, u64)>,
+}
+
+// Hash and displace: buckets are placed largest first, each trying
+// displacement pairs until all of its keys land in free slots.
+#[cfg(feature = "alloc")]
+pub(crate) fn build(entries: &mut [(u64, u64)]) -> Option<Perfect> {
+    entries.sort_unstable_by_key(|e| e.0);
+    if entries.windows(2).any(|w| w[0].0 == w[1].0) {
+        return None;
+    }
+
+    let n = entries.len();
+    let nbuckets = n.div_ceil(LAMBDA);
+
+    for seed in 0.. {
+        if let Some(perfect) = try_build(entries, seed, nbuckets) {
+            return Some(perfect);
+        }
+    }
+    unreachable!()
+}
+
+#[cfg(feature = "alloc")]
+fn try_build(entries: &[(u64, u64)], seed: u64, nbuckets: usize) -> Option<Perfect> {
+    let n = entries.len();
+    let hashes: Vec<KeyHashes> = entries
+        .iter()
+        .map(|&(hash, _)| KeyHashes::new(seed, hash))
+        .collect();
+
+    let mut buckets: Vec<Vec<usize>> = vec![Vec::new(); nbuckets];
+    for (i, h) in hashes.iter().enumerate() {
+        buckets[(h.bucket % nbuckets as u64) as usize].push(i);
+    }
+    let mut order: Vec<usize> = (0..nbuckets).collect();
+    order.sort_unstable_by_key(|&b| core::cmp::Reverse(buckets[b].len()));
+
+    let mut taken = vec![false; n];
+    let mut displacements = vec![(0, 0); nbuckets];
+    let mut placed = Vec::new();
+    let mut free = 0;
+
+    for b in order {
+        let keys = &buckets[b];
+        if keys.is_empty() {
+            break;
+        }
+
+        // A lone key can be sent straight to any free slot.
+        if let [k] = keys[..] {
+            while taken[free] {
+                free += 1;
+            }
+            taken[free] = true;
+            displacements[b] = (
+                0,
+                (free as u64 + n as u64 - hashes[k].f2 % n as u64) % n as u64,
+            );
+            continue;
+        }
+
+        // Both displacements change on every attempt: with one held fixed,
+        // a key whose f1 shares a large factor with n only reaches a few
+        // slots.
+        let found = (0..MAX_DISPLACEMENTS as u64)
+            .map(|i| (i % n as u64, mix(i) % n as u64))
+            .find(|&(d1, d2)| {
+                placed.clear();
+                for &k in keys {
+                    let slot = hashes[k].slot(d1, d2, n as u64) as usize;
+                    if taken[slot] || placed.contains(&slot) {
+                        return false;
+                    }
+                    placed.push(slot);
+                }
+                true
+            });
+
+        let (d1, d2) = found?;
+        for &slot in &placed {
+            taken[slot] = true;
+        }
+        displacements[b] = (d1, d2);
+    }
+
+    let mut slots = vec![(Hash(0), 0); n];
+    for (i, &(hash, pos)) in entries.iter().enumerate() {
+        let h = hashes[i];
+        let (d1, d2) = displacements[(h.bucket % nbuckets as u64) as usize];
+        slots[h.slot(d1, d2, n as u64) as usize] = (check_hash(hash), pos);
+    }
+
+    Some(Perfect {
+        seed,
+        displacements,
+        slots,
+    })
+}
+
+pub struct PerfectReader<A, L: Layout = Classic> {
+    inner: Reader<A, L>,
+    seed: u64,
+    buckets: PosLen,
+    slots: PosLen,
+}
+
+impl<A, L: Layout> core::fmt::Debug for PerfectReader<A, L> {
+    fn fmt(&self, fmt: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        write!(fmt, "PerfectReader {{}}")
+    }
+}
+
+type KeyValue<'c, A> = (<A as CDBAccess>::Data<'c>, <A as CDBAccess>::Data<'c>);
+type PosKeyValue<'c, A> = (
+    usize,
+    <A as CDBAccess>::Data<'c>,
+    <A as CDBAccess>::Data<'c>,
+);
+
+impl<A: CDBAccess> PerfectReader<A> {
+    pub fn new(access: A) -> Result<PerfectReader<A>, ReadError> {
+        Self::with_layout(access)
+    }
+}
+
+impl<A: CDBAccess, L: Layout> PerfectReader<A, L> {
+    pub fn with_layout(access: A) -> Result<PerfectReader<A, L>, ReadError> {
+        let (layout, seed, buckets, slots) = Self::read_header(&access)?;
+        let mut tables = L::empty_tables();
+        tables.as_mut()[0] = PosLen {
+            pos: buckets.pos,
+            len: 0,
+        };
+
+        Ok(PerfectReader {
+            inner: Reader::from_parts(access, layout, tables),
+            seed,
+            buckets,
+            slots,
+        })
+    }
+
+    fn read_header(access: &A) -> Result<(L, u64, PosLen, PosLen), ReadError> {
+        let header = access.read_at(0, header_len::<L>(access.len()))?;
+        let header = header.as_ref();
+        let layout = L::from_header(header, access.len())?;
+
+        let word = |i: usize| {
+            let pos = i * L::WORD_SIZE;
+            L::read_word(&header[pos..pos + L::WORD_SIZE])
+        };
+        let to_usize = |w: u64| usize::try_from(w).unwrap_or(usize::MAX);
+        if word(2) != MAGIC || (0..L::ENTRIES).any(|i| word(2 * i + 1) != 0) {
+            return Err(ReadError::BadMagic);
+        }
+
+        let end = to_usize(word(0));
+        let buckets = PosLen {
+            pos: end,
+            len: to_usize(word(4)),
+        };
+        let slots = PosLen {
+            pos: buckets
+                .len
+                .checked_mul(L::PAIR_SIZE)
+                .and_then(|sz| sz.checked_add(end))
+                .unwrap_or(usize::MAX),
+            len: to_usize(word(6)),
+        };
+        for (table, t) in [buckets, slots].iter().enumerate() {
+            if t.pos < L::HEADER_SIZE || !t.valid(access.len(), L::PAIR_SIZE) {
+                return Err(ReadError::TableOutOfRange {
+                    table,
+                    pos: t.pos,
+                    len: t.len,
+                });
+            }
+        }
+        if (buckets.len == 0) != (slots.len == 0) {
+            return Err(ReadError::EmptyHeader);
+        }
+
+        Ok((layout, word(8), buckets, slots))
+    }
+
+    pub fn get_with_offset<'c>(
+        &'c self,
+        key: &[u8],
+    ) -> Result<Option<(usize, A::Data<'c>)>, ReadError> {
+        if self.slots.len == 0 {
+            return Ok(None);
+        }
+
+        let hash = key_hash(key);
+        let h = KeyHashes::new(self.seed, hash);
+        let bucket = (h.bucket % self.buckets.len as u64) as usize;
+        let (d1, d2) = self
+            .inner
+            .read_pair(self.buckets.pos + bucket * L::PAIR_SIZE)?;
+        let slot = h.slot(d1, d2, self.slots.len as u64) as usize;
+
+        let (stored, ptr) = self
+            .inner
+            .read_hash_pos(self.slots.pos + slot * L::PAIR_SIZE)?;
+        if stored != check_hash(hash) {
+            return Ok(None);
+        }
+        if ptr < L::HEADER_SIZE || ptr >= self.inner.records_end() {
+            return Err(ReadError::HashPointerOutOfRange {
+                table: 0,
+                slot,
+                ptr,
+            });
+        }
+
+        Ok(self.inner.read_value_if_key(ptr, key)?.map(|v| (ptr, v)))
+    }
+
+    pub fn get<'c>(&'c self, key: &[u8]) -> Result<Option<A::Data<'c>>, ReadError> {
+        Ok(self.get_with_offset(key)?.map(|(_, v)| v))
+    }
+
+    pub fn iter(&self) -> PerfectIter<'_, A, L> {
+        PerfectIter {
+            cdb: self,
+            state: IterState::default(),
+        }
+    }
+
+    pub fn iter_with_offsets(&self) -> impl Iterator<Item = Result<PosKeyValue<'_, A>, ReadError>> {
+        let mut state = IterState::default();
+        core::iter::from_fn(move || {
+            state.next_unpadded(&self.inner, self.inner.records_end(), |pos| {
+                self.is_indexed_empty(pos)
+            })
+        })
+    }
+
+    // Keys are unique, so one lookup finds the only indexed empty key.
+    fn is_indexed_empty(&self, pos: usize) -> Result<bool, ReadError> {
+        Ok(self.get_with_offset(b"")?.is_some_and(|(p, _)| p == pos))
+    }
+
+    pub fn record_at(&self, offset: usize) -> Result<KeyValue<'_, A>, ReadError> {
+        self.inner.record_at(offset)
+    }
+
+    pub fn len(&self) -> usize {
+        self.slots.len
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.slots.len == 0
+    }
+
+    pub fn layout(&self) -> &L {
+        self.inner.layout()
+    }
+
+    pub fn access(&self) -> &A {
+        self.inner.access()
+    }
+
+    pub fn into_access(self) -> A {
+        self.inner.into_access()
+    }
+}
+
+pub struct PerfectIter<'a, A, L: Layout = Classic> {
+    cdb: &'a PerfectReader<A, L>,
+    state: IterState,
+}
+
+impl<'a, A, L: Layout> Clone for PerfectIter<'a, A, L> {
+    fn clone(&self) -> Self {
+        PerfectIter {
+            cdb: self.cdb,
+            state: self.state,
+        }
+    }
+}
+
+impl<'a, A: CDBAccess, L: Layout> Iterator for PerfectIter<'a, A, L> {
+    type Item = Result<KeyValue<'a, A>, ReadError>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        let cdb = self.cdb;
+        self.state
+            .next_unpadded(&cdb.inner, cdb.inner.records_end(), |pos| {
+                cdb.is_indexed_empty(pos)
+            })
+            .map(|r| r.map(|(_, k, v)| (k, v)))
+    }
+}
+
+impl<'a, A: CDBAccess, L: Layout> IntoIterator for &'a PerfectReader<A, L> {
+    type IntoIter = PerfectIter<'a, A, L>;
+    type Item = <PerfectIter<'a, A, L> as Iterator>::Item;
+
+    fn into_iter(self) -> PerfectIter<'a, A, L> {
+        self.iter()
+    }
+}
+
+#[cfg(all(test, feature = "alloc"))]
+mod tests {
+    use super::*;
+    use crate::read::tests::{fill, KEYS};
+    use crate::{MemWriter, WriteError};
+
+    fn build() -> Vec<u8> {
+        let mut writer = MemWriter::perfect(Vec::new()).unwrap();
+        fill(&mut writer, false);
+        writer.finish_perfect().unwrap()
+    }
+
+    #[test]
+    fn hits_and_misses() {
+        let data = build();
+        let cdb = PerfectReader::new(&data[..]).unwrap();
+
+        assert_eq!(cdb.len(), KEYS as usize);
+        for i in 0..KEYS {
+            assert_eq!(
+                cdb.get(&i.to_le_bytes()).unwrap(),
+                Some(&(i * 3).to_le_bytes()[..])
+            );
+        }
+        for i in KEYS..KEYS * 4 {
+            assert_eq!(cdb.get(&i.to_le_bytes()).unwrap(), None);
+        }
+        assert_eq!(cdb.iter().count(), KEYS as usize);
+    }
+
+    #[test]
+    fn not_a_cdb_index() {
+        let data = build();
+        // The index magic sits where a CDB reader expects table 1.
+        assert!(matches!(
+            Reader::new(&data[..]),
+            Err(ReadError::TableOutOfRange { table: 1, .. })
+        ));
+    }
+
+    #[test]
+    fn duplicate_keys() {
+        let mut writer = MemWriter::perfect(Vec::new()).unwrap();
+        writer.write(b"key", b"1").unwrap();
+        writer.write(b"key", b"2").unwrap();
+        assert_eq!(writer.finish_perfect(), Err(WriteError::DuplicateKey));
+    }
+
+    #[test]
+    fn requires_perfect_writer() {
+        let mut writer = MemWriter::new(Vec::new()).unwrap();
+        writer.write(b"key", b"1").unwrap();
+        assert_eq!(writer.finish_perfect(), Err(WriteError::NotPerfect));
+    }
+
+    #[test]
+    fn padding_skipped() {
+        let mut writer = MemWriter::perfect(Vec::new()).unwrap();
+        writer.set_alignment(16);
+        fill(&mut writer, false);
+        writer.write(b"", b"").unwrap();
+        let data = writer.finish_perfect().unwrap();
+        let cdb = PerfectReader::new(&data[..]).unwrap();
+
+        assert_eq!(cdb.iter().count(), KEYS as usize + 1);
+        assert_eq!(cdb.get(b"").unwrap(), Some(&b""[..]));
+    }
+}
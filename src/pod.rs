@@ -0,0 +1,67 @@
+use core::mem::{align_of, size_of};
+
+use super::*;
+
+/// # Safety
+///
+/// Implementors must be `Copy` types without padding bytes for which every
+/// bit pattern is a valid value.
+pub unsafe trait Pod: Copy + 'static {}
+
+macro_rules! impl_pod {
+    ($($t:ty),*) => {
+        $(unsafe impl Pod for $t {})*
+    };
+}
+
+impl_pod!(u8, u16, u32, u64, u128, usize, i8, i16, i32, i64, i128, isize, f32, f64);
+
+unsafe impl<T: Pod, const N: usize> Pod for [T; N] {}
+
+fn aligned<T: Pod>(data: &[u8]) -> bool {
+    (data.as_ptr() as usize).is_multiple_of(align_of::<T>())
+}
+
+pub fn cast<T: Pod>(data: &[u8]) -> Option<&T> {
+    if data.len() != size_of::<T>() || !aligned::<T>(data) {
+        return None;
+    }
+    Some(unsafe { &*(data.as_ptr() as *const T) })
+}
+
+pub fn cast_slice<T: Pod>(data: &[u8]) -> Option<&[T]> {
+    let size = size_of::<T>();
+    if size == 0 || !data.len().is_multiple_of(size) || !aligned::<T>(data) {
+        return None;
+    }
+    Some(unsafe { core::slice::from_raw_parts(data.as_ptr() as *const T, data.len() / size) })
+}
+
+impl<A: AsRef<[u8]>, L: Layout> Reader<A, L> {
+    fn pod_error<T>(&self, value: &[u8]) -> ReadError {
+        ReadError::PodMismatch {
+            pos: value.as_ptr() as usize - self.access().as_ref().as_ptr() as usize,
+            len: value.len(),
+            size: size_of::<T>(),
+            align: align_of::<T>(),
+        }
+    }
+
+    pub fn get_pod<T: Pod>(&self, key: &[u8]) -> Result<Option<&T>, ReadError> {
+        match self.get(key)? {
+            Some(value) => cast(value)
+                .map(Some)
+                .ok_or_else(|| self.pod_error::<T>(value)),
+            None => Ok(None),
+        }
+    }
+
+    pub fn get_pod_slice<T: Pod>(&self, key: &[u8]) -> Result<Option<&[T]>, ReadError> {
+        match self.get(key)? {
+            Some(value) => cast_slice(value)
+                .map(Some)
+                .ok_or_else(|| self.pod_error::<T>(value)),
+            None => Ok(None),
+        }
+    }
+}
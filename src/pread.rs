@@ -0,0 +1,328 @@
+use std::collections::HashMap;
+use std::convert::TryFrom;
+use std::fs::File;
+use std::os::unix::fs::FileExt;
+use std::path::Path;
+use std::sync::Mutex;
+
+use super::*;
+
+const DEFAULT_PAGE_SIZE: usize = 4096;
+const DEFAULT_CACHE_PAGES: usize = 256;
+
+struct Page {
+    number: u64,
+    data: Vec<u8>,
+    last_use: u64,
+}
+
+struct PageCache {
+    page_size: usize,
+    capacity: usize,
+    index: HashMap<u64, usize>,
+    pages: Vec<Page>,
+    clock: u64,
+}
+
+impl PageCache {
+    fn new(page_size: usize, capacity: usize) -> Self {
+        PageCache {
+            page_size,
+            capacity,
+            index: HashMap::with_capacity(capacity),
+            pages: Vec::with_capacity(capacity),
+            clock: 0,
+        }
+    }
+
+    fn page(&mut self, file: &File, number: u64) -> std::io::Result<&[u8]> {
+        self.clock += 1;
+
+        if let Some(&idx) = self.index.get(&number) {
+            let page = &mut self.pages[idx];
+            page.last_use = self.clock;
+            return Ok(&page.data);
+        }
+
+        let idx = if self.pages.len() < self.capacity {
+            self.pages.push(Page {
+                number,
+                data: Vec::with_capacity(self.page_size),
+                last_use: 0,
+            });
+            self.pages.len() - 1
+        } else {
+            let (idx, oldest) = self
+                .pages
+                .iter()
+                .enumerate()
+                .min_by_key(|(_, page)| page.last_use)
+                .unwrap();
+            // A slot whose read failed is not indexed, and its page may
+            // have been loaded into another slot since.
+            if self.index.get(&oldest.number) == Some(&idx) {
+                self.index.remove(&oldest.number);
+            }
+            idx
+        };
+
+        let page = &mut self.pages[idx];
+        page.number = number;
+        page.last_use = self.clock;
+        page.data.resize(self.page_size, 0);
+        let filled = match read_full_at(file, &mut page.data, number * self.page_size as u64) {
+            Ok(filled) => filled,
+            Err(e) => {
+                // Leave the slot unused so it gets recycled first.
+                page.data.clear();
+                page.last_use = 0;
+                return Err(e);
+            }
+        };
+        page.data.truncate(filled);
+        self.index.insert(number, idx);
+
+        Ok(&page.data)
+    }
+
+    fn read_exact_at(&mut self, file: &File, pos: u64, buf: &mut [u8]) -> std::io::Result<()> {
+        if buf.len() >= self.page_size {
+            return file.read_exact_at(buf, pos);
+        }
+
+        let page_size = self.page_size as u64;
+        let mut done = 0;
+
+        while done < buf.len() {
+            let cur = pos + done as u64;
+            let offset = (cur % page_size) as usize;
+            let page = self.page(file, cur / page_size)?;
+
+            let avail = page.get(offset..).unwrap_or(&[]);
+            if avail.is_empty() {
+                return Err(std::io::ErrorKind::UnexpectedEof.into());
+            }
+            let n = avail.len().min(buf.len() - done);
+            buf[done..done + n].copy_from_slice(&avail[..n]);
+            done += n;
+        }
+
+        Ok(())
+    }
+}
+
+fn read_full_at(file: &File, buf: &mut [u8], pos: u64) -> std::io::Result<usize> {
+    let mut filled = 0;
+
+    while filled < buf.len() {
+        match file.read_at(&mut buf[filled..], pos + filled as u64) {
+            Ok(0) => break,
+            Ok(n) => filled += n,
+            Err(ref e) if e.kind() == std::io::ErrorKind::Interrupted => (),
+            Err(e) => return Err(e),
+        }
+    }
+
+    Ok(filled)
+}
+
+pub type FileReader<L = Classic> = Reader<FileAccess, L>;
+
+pub struct FileAccess {
+    file: File,
+    len: usize,
+    cache: Mutex<PageCache>,
+}
+
+impl std::fmt::Debug for FileAccess {
+    fn fmt(&self, fmt: &mut std::fmt::Formatter<'_>) -> std::result::Result<(), std::fmt::Error> {
+        write!(fmt, "FileAccess {{ len: {} }}", self.len)
+    }
+}
+
+impl FileAccess {
+    pub fn open<P: AsRef<Path>>(path: P) -> std::io::Result<Self> {
+        Self::new(File::open(path)?)
+    }
+
+    pub fn new(file: File) -> std::io::Result<Self> {
+        Self::with_cache(file, DEFAULT_PAGE_SIZE, DEFAULT_CACHE_PAGES)
+    }
+
+    pub fn with_cache(file: File, page_size: usize, pages: usize) -> std::io::Result<Self> {
+        assert!(page_size > 0, "page size must be non-zero");
+        assert!(pages > 0, "page cache must hold at least one page");
+
+        let len = file.metadata()?.len();
+        let len = usize::try_from(len).unwrap_or(usize::MAX);
+
+        Ok(FileAccess {
+            file,
+            len,
+            cache: Mutex::new(PageCache::new(page_size, pages)),
+        })
+    }
+
+    pub fn file(&self) -> &File {
+        &self.file
+    }
+
+    pub fn into_file(self) -> File {
+        self.file
+    }
+}
+
+impl CDBAccess for FileAccess {
+    type Data<'a> = Vec<u8>;
+
+    fn len(&self) -> usize {
+        self.len
+    }
+
+    fn read_at(&self, pos: usize, len: usize) -> Result<Vec<u8>, ReadError> {
+        check_bounds(self, pos, len)?;
+        let mut data = vec![0u8; len];
+        self.read_exact_at(pos, &mut data)?;
+        Ok(data)
+    }
+
+    fn read_exact_at(&self, pos: usize, buf: &mut [u8]) -> Result<(), ReadError> {
+        check_bounds(self, pos, buf.len())?;
+
+        let mut cache = self.cache.lock().unwrap_or_else(|e| e.into_inner());
+        Ok(cache.read_exact_at(&self.file, pos as u64, buf)?)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::path::PathBuf;
+
+    struct TempFile(PathBuf);
+
+    impl TempFile {
+        fn new(name: &str, data: &[u8]) -> Self {
+            let path =
+                std::env::temp_dir().join(format!("cordoba-pread-{}-{}", std::process::id(), name));
+            std::fs::write(&path, data).unwrap();
+            TempFile(path)
+        }
+
+        fn open(&self) -> File {
+            File::open(&self.0).unwrap()
+        }
+
+        // Reads from a write-only handle fail.
+        fn open_unreadable(&self) -> File {
+            std::fs::OpenOptions::new()
+                .write(true)
+                .open(&self.0)
+                .unwrap()
+        }
+    }
+
+    impl Drop for TempFile {
+        fn drop(&mut self) {
+            let _ = std::fs::remove_file(&self.0);
+        }
+    }
+
+    fn pattern(len: usize) -> Vec<u8> {
+        (0..len).map(|i| (i % 251) as u8).collect()
+    }
+
+    fn cached(cache: &PageCache) -> Vec<u64> {
+        let mut pages: Vec<u64> = cache.index.keys().copied().collect();
+        pages.sort_unstable();
+        pages
+    }
+
+    #[test]
+    fn evicts_least_recently_used() {
+        let tmp = TempFile::new("lru", &pattern(160));
+        let file = tmp.open();
+        let mut cache = PageCache::new(16, 2);
+
+        for number in [0, 1, 0, 2] {
+            cache.page(&file, number).unwrap();
+        }
+        assert_eq!(cached(&cache), [0, 2]);
+
+        cache.page(&file, 3).unwrap();
+        assert_eq!(cached(&cache), [2, 3]);
+        assert_eq!(cache.page(&file, 3).unwrap(), &pattern(160)[48..64]);
+    }
+
+    #[test]
+    fn failed_read_slot_reused_first() {
+        let tmp = TempFile::new("failed", &pattern(160));
+        let (file, bad) = (tmp.open(), tmp.open_unreadable());
+        let mut cache = PageCache::new(16, 2);
+
+        cache.page(&file, 0).unwrap();
+        cache.page(&file, 1).unwrap();
+        assert!(cache.page(&bad, 2).is_err());
+        assert_eq!(cached(&cache), [1]);
+
+        // The failed slot goes before page 1, the least recently used.
+        cache.page(&file, 3).unwrap();
+        assert_eq!(cached(&cache), [1, 3]);
+        assert_eq!(cache.pages.len(), 2);
+    }
+
+    #[test]
+    fn failed_page_loaded_elsewhere() {
+        let tmp = TempFile::new("elsewhere", &pattern(160));
+        let (file, bad) = (tmp.open(), tmp.open_unreadable());
+        let mut cache = PageCache::new(16, 3);
+
+        assert!(cache.page(&bad, 5).is_err());
+        cache.page(&file, 5).unwrap();
+        cache.page(&file, 6).unwrap();
+        // Recycling the failed slot must not drop the good copy of page 5.
+        cache.page(&file, 7).unwrap();
+        assert_eq!(cached(&cache), [5, 6, 7]);
+    }
+
+    #[test]
+    fn reads_across_pages() {
+        let data = pattern(100);
+        let tmp = TempFile::new("across", &data);
+        let file = tmp.open();
+        let mut cache = PageCache::new(16, 2);
+
+        let mut buf = [0u8; 12];
+        cache.read_exact_at(&file, 10, &mut buf).unwrap();
+        assert_eq!(buf, data[10..22]);
+
+        let mut big = [0u8; 40];
+        cache.read_exact_at(&file, 30, &mut big).unwrap();
+        assert_eq!(big[..], data[30..70]);
+
+        let err = cache.read_exact_at(&file, 95, &mut buf).unwrap_err();
+        assert_eq!(err.kind(), std::io::ErrorKind::UnexpectedEof);
+    }
+
+    #[test]
+    fn file_reader() {
+        let mut writer = crate::MemWriter::new(Vec::new()).unwrap();
+        for i in 0..500u32 {
+            writer
+                .write(&i.to_le_bytes(), &pattern(i as usize % 40))
+                .unwrap();
+        }
+        let tmp = TempFile::new("reader", &writer.finish().unwrap());
+
+        let access = FileAccess::with_cache(tmp.open(), 64, 4).unwrap();
+        let cdb = FileReader::new(access).unwrap();
+        for i in 0..500u32 {
+            let value = cdb.get(&i.to_le_bytes()).unwrap().unwrap();
+            assert_eq!(value, pattern(i as usize % 40));
+        }
+        assert!(matches!(
+            cdb.access().read_at(cdb.access().len() - 4, 8),
+            Err(ReadError::OutOfBounds { .. })
+        ));
+    }
+}
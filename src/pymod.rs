@@ -1,21 +1,20 @@
-
 use std::cell::RefCell;
 use std::fs::{File, OpenOptions};
 use std::io::{BufWriter, Write};
 use std::os::unix::io::AsRawFd;
 
-use memmap::Mmap;
-
 use pyo3::exceptions::{KeyError, ValueError};
 use pyo3::prelude::*;
 use pyo3::types::PyBytes;
 use pyo3::{PyIterProtocol, PyMappingProtocol, PySequenceProtocol};
 
-use crate::{IterState, LookupState, Reader as CDBReader, Writer as CDBWriter};
+use crate::{
+    IterState, LookupState, MapOptions, MappedFile, Reader as CDBReader, Writer as CDBWriter,
+};
 
 #[pyclass]
 pub struct Reader {
-    inner: CDBReader<Mmap>,
+    inner: CDBReader<MappedFile>,
 }
 
 #[pymethods]
@@ -23,9 +22,7 @@ impl Reader {
     #[new]
     fn new(fname: PyObject, py: Python<'_>) -> PyResult<Self> {
         let path: &str = py.import("os")?.call1("fsdecode", (fname,))?.extract()?;
-        let file = File::open(path)?;
-        let map = unsafe { Mmap::map(&file) }?;
-        let reader = CDBReader::new(map)?;
+        let reader = CDBReader::open(path, &MapOptions::new())?;
         Ok(Reader { inner: reader })
     }
 
@@ -217,10 +214,11 @@ impl<'p> PyContextProtocol<'p> for Writer {
 impl core::convert::From<crate::ReadError> for pyo3::PyErr {
     fn from(error: crate::ReadError) -> Self {
         match error {
-            crate::ReadError::OutOfBounds => {
-                pyo3::exceptions::EOFError::py_err("Tried to read beyond end of file.")
+            crate::ReadError::OutOfBounds { .. } => {
+                pyo3::exceptions::EOFError::py_err(error.to_string())
             }
-            crate::ReadError::InvalidFile => pyo3::exceptions::IOError::py_err("Invalid file data."),
+            crate::ReadError::Io(e) => e.into(),
+            _ => pyo3::exceptions::IOError::py_err(error.to_string()),
         }
     }
 }
@@ -1,104 +1,356 @@
-use core::convert::TryInto;
+use core::convert::TryFrom;
 use core::iter::Chain;
 use core::ops::Range;
 
+use super::layout::{header_len, MAX_PAIR_SIZE};
 use super::*;
 
 #[derive(Debug)]
+#[non_exhaustive]
 pub enum ReadError {
-    OutOfBounds,
-    InvalidFile,
+    OutOfBounds {
+        pos: usize,
+        len: usize,
+    },
+    TableOutOfRange {
+        table: usize,
+        pos: usize,
+        len: usize,
+    },
+    EmptyHeader,
+    BadMagic,
+    PodMismatch {
+        pos: usize,
+        len: usize,
+        size: usize,
+        align: usize,
+    },
+    RecordOverflow {
+        record: usize,
+        klen: usize,
+        vlen: usize,
+    },
+    RecordPastEnd {
+        record: usize,
+        end: usize,
+    },
+    HashPointerOutOfRange {
+        table: usize,
+        slot: usize,
+        ptr: usize,
+    },
+    BadCursor {
+        token: u64,
+    },
+    NoSuchTable {
+        table: usize,
+        tables: usize,
+    },
+    #[cfg(feature = "std")]
+    Io(std::io::Error),
 }
 
 type CDBResult<T> = Result<T, ReadError>;
-pub trait CDBAccess: AsRef<[u8]> {}
-impl<T: AsRef<[u8]>> CDBAccess for T {}
 
-pub struct Reader<A> {
+pub trait CDBAccess {
+    type Data<'a>: AsRef<[u8]>
+    where
+        Self: 'a;
+
+    fn len(&self) -> usize;
+
+    fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    fn read_at(&self, pos: usize, len: usize) -> CDBResult<Self::Data<'_>>;
+
+    fn read_exact_at(&self, pos: usize, buf: &mut [u8]) -> CDBResult<()> {
+        buf.copy_from_slice(self.read_at(pos, buf.len())?.as_ref());
+        Ok(())
+    }
+
+    fn prefetch(&self, _pos: usize, _len: usize) {}
+}
+
+// Lengths come from the file, so backends check them against the file
+// size before slicing or allocating a buffer.
+pub(crate) fn check_bounds<A: CDBAccess>(access: &A, pos: usize, len: usize) -> CDBResult<()> {
+    if pos.checked_add(len).is_none_or(|end| end > access.len()) {
+        return Err(ReadError::OutOfBounds { pos, len });
+    }
+    Ok(())
+}
+
+impl<T: AsRef<[u8]>> CDBAccess for T {
+    type Data<'a>
+        = &'a [u8]
+    where
+        T: 'a;
+
+    #[inline]
+    fn len(&self) -> usize {
+        self.as_ref().len()
+    }
+
+    #[inline]
+    fn read_at(&self, pos: usize, len: usize) -> CDBResult<&[u8]> {
+        check_bounds(self, pos, len)?;
+        Ok(&self.as_ref()[pos..pos + len])
+    }
+
+    #[cfg(unix)]
+    fn prefetch(&self, pos: usize, len: usize) {
+        let data = match pos
+            .checked_add(len)
+            .and_then(|end| self.as_ref().get(pos..end))
+        {
+            Some(data) if !data.is_empty() => data,
+            _ => return,
+        };
+
+        let page = match unsafe { libc::sysconf(libc::_SC_PAGESIZE) } {
+            n if n > 0 => n as usize,
+            _ => return,
+        };
+        let start = data.as_ptr() as usize;
+        let aligned = start - start % page;
+
+        // Purely advisory: failures (e.g. on memory that is not a mapping) are ignored.
+        unsafe {
+            libc::madvise(
+                aligned as *mut libc::c_void,
+                start + data.len() - aligned,
+                libc::MADV_WILLNEED,
+            );
+        }
+    }
+}
+
+pub struct Reader<A, L: Layout = Classic> {
     access: A,
-    tables: [PosLen; ENTRIES],
+    layout: L,
+    tables: L::Tables,
+    robinhood: bool,
 }
 
-impl<A> std::fmt::Debug for Reader<A> {
-    fn fmt(&self, fmt: &mut std::fmt::Formatter<'_>) -> std::result::Result<(), std::fmt::Error> {
+impl<A, L: Layout> core::fmt::Debug for Reader<A, L> {
+    fn fmt(&self, fmt: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
         write!(fmt, "Reader {{}}")
     }
 }
 
-#[derive(Clone, Copy)]
-pub struct IterState(usize);
+#[derive(Clone, Copy, Default)]
+pub struct IterState(pub(crate) usize);
+
+// A saved iteration position: the file offset of the next record, or zero
+// for the start of the file.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Hash)]
+pub struct Cursor(u64);
+
+impl Cursor {
+    pub fn from_token(token: u64) -> Self {
+        Cursor(token)
+    }
 
-impl Default for IterState {
-    fn default() -> Self {
-        IterState(ENTRIES * PAIR_SIZE)
+    pub fn token(self) -> u64 {
+        self.0
     }
 }
 
-#[derive(Clone)]
-pub struct FileIter<'a, A> {
-    cdb: &'a Reader<A>,
+pub struct FileIter<'a, A, L: Layout = Classic> {
+    cdb: &'a Reader<A, L>,
     state: IterState,
+    empty: EmptyKeys,
+    failed: Option<usize>,
+}
+
+impl<'a, A, L: Layout> Clone for FileIter<'a, A, L> {
+    fn clone(&self) -> Self {
+        FileIter {
+            cdb: self.cdb,
+            state: self.state,
+            empty: self.empty.clone(),
+            failed: self.failed,
+        }
+    }
 }
 
-impl<'a, A> FileIter<'a, A> {
-    fn new(cdb: &'a Reader<A>) -> Self {
+impl<'a, A, L: Layout> FileIter<'a, A, L> {
+    fn new(cdb: &'a Reader<A, L>) -> Self {
+        Self::starting_at(cdb, 0)
+    }
+
+    fn starting_at(cdb: &'a Reader<A, L>, pos: usize) -> Self {
         FileIter {
             cdb,
-            state: Default::default(),
+            state: IterState(pos),
+            empty: EmptyKeys::default(),
+            failed: None,
         }
     }
+
+    // After an error the iterator is exhausted, but the cursor still
+    // points at the record that failed so that a resumed scan retries it.
+    pub fn cursor(&self) -> Cursor {
+        Cursor(self.failed.unwrap_or(self.state.0) as u64)
+    }
 }
 
+type KeyValue<'c, A> = (<A as CDBAccess>::Data<'c>, <A as CDBAccess>::Data<'c>);
+type PosKeyValue<'c, A> = (
+    usize,
+    <A as CDBAccess>::Data<'c>,
+    <A as CDBAccess>::Data<'c>,
+);
+
 impl IterState {
     #[inline]
-    pub fn next<'c, A: CDBAccess>(
+    pub fn next<'c, A: CDBAccess, L: Layout>(
         &mut self,
-        cdb: &'c Reader<A>,
-    ) -> Option<CDBResult<(&'c [u8], &'c [u8])>> {
-        if self.0 < cdb.tables[0].pos {
-            match cdb.get_key_and_value(self.0) {
+        cdb: &'c Reader<A, L>,
+    ) -> Option<CDBResult<KeyValue<'c, A>>> {
+        self.next_record(cdb).map(|r| r.map(|(_, k, v)| (k, v)))
+    }
+
+    pub fn next_record<'c, A: CDBAccess, L: Layout>(
+        &mut self,
+        cdb: &'c Reader<A, L>,
+    ) -> Option<CDBResult<PosKeyValue<'c, A>>> {
+        self.next_unpadded(cdb, cdb.records_end(), |pos| cdb.is_indexed_empty(pos))
+    }
+
+    // Skips alignment padding: empty-key records shaped like filler that
+    // `is_indexed` reports no table slot points to.
+    pub(crate) fn next_unpadded<'c, A, L, P>(
+        &mut self,
+        cdb: &'c Reader<A, L>,
+        end: usize,
+        mut is_indexed: P,
+    ) -> Option<CDBResult<PosKeyValue<'c, A>>>
+    where
+        A: CDBAccess,
+        L: Layout,
+        P: FnMut(usize) -> CDBResult<bool>,
+    {
+        self.0 = self.0.max(L::HEADER_SIZE);
+
+        while self.0 < end {
+            let record = self.0;
+            match cdb.get_key_and_value(record) {
+                Ok((_, _, newpos)) if newpos > end => {
+                    self.0 = end;
+                    return Some(Err(ReadError::RecordPastEnd { record, end }));
+                }
                 Ok((k, v, newpos)) => {
                     self.0 = newpos;
-                    Some(Ok((k, v)))
+                    if k.as_ref().is_empty() {
+                        match cdb
+                            .padding_shape(record, newpos)
+                            .and_then(|shape| Ok(shape && !is_indexed(record)?))
+                        {
+                            Ok(true) => continue,
+                            Ok(false) => (),
+                            Err(e) => return Some(Err(e)),
+                        }
+                    }
+                    return Some(Ok((record, k, v)));
                 }
                 Err(e) => {
-                    self.0 = cdb.tables[0].pos;
-                    Some(Err(e))
+                    self.0 = end;
+                    return Some(Err(e));
                 }
             }
-        } else {
-            None
         }
+        None
     }
 }
 
-impl<'a, A: CDBAccess> Iterator for FileIter<'a, A> {
-    type Item = CDBResult<(&'a [u8], &'a [u8])>;
+impl<'a, A: CDBAccess, L: Layout> Iterator for FileIter<'a, A, L> {
+    type Item = CDBResult<KeyValue<'a, A>>;
 
     fn next(&mut self) -> Option<Self::Item> {
-        self.state.next(self.cdb)
+        let (cdb, empty) = (self.cdb, &mut self.empty);
+        let pos = self.state.0;
+        let item = self
+            .state
+            .next_unpadded(cdb, cdb.records_end(), |pos| empty.indexed(cdb, pos))
+            .map(|r| r.map(|(_, k, v)| (k, v)));
+        if let Some(Err(_)) = item {
+            self.failed = Some(pos);
+        }
+        item
     }
 }
 
-#[derive(Clone)]
-struct LookupIter<'c, 'k, A> {
-    cdb: &'c Reader<A>,
+// Offsets of the empty-key records a hash table points to, gathered the
+// first time an iterator needs them. Without alloc every check looks
+// them up again.
+#[derive(Clone, Default)]
+pub(crate) struct EmptyKeys {
+    #[cfg(feature = "alloc")]
+    offsets: Option<alloc::vec::Vec<usize>>,
+}
+
+impl EmptyKeys {
+    pub(crate) fn indexed<A: CDBAccess, L: Layout>(
+        &mut self,
+        cdb: &Reader<A, L>,
+        pos: usize,
+    ) -> CDBResult<bool> {
+        #[cfg(feature = "alloc")]
+        {
+            let offsets = match &mut self.offsets {
+                Some(offsets) => offsets,
+                offsets => {
+                    let mut found = cdb
+                        .lookup_with_offsets(b"")
+                        .map(|r| r.map(|(pos, _)| pos))
+                        .collect::<CDBResult<alloc::vec::Vec<_>>>()?;
+                    found.sort_unstable();
+                    offsets.insert(found)
+                }
+            };
+            Ok(offsets.binary_search(&pos).is_ok())
+        }
+        #[cfg(not(feature = "alloc"))]
+        cdb.is_indexed_empty(pos)
+    }
+}
+
+// Alignment padding moves the next value to an aligned offset. Writers
+// never pad by a full alignment or more beyond a record head, and the
+// largest power of two dividing the next value offset is at least the
+// alignment that was used.
+pub(crate) fn filler_shape<L: Layout>(pos: usize, next: usize, next_value: usize) -> bool {
+    let gap = next - pos - L::PAIR_SIZE;
+    1usize
+        .checked_shl(next_value.trailing_zeros())
+        .is_none_or(|align| gap < align)
+}
+
+struct LookupIter<'c, 'k, A, L: Layout> {
+    cdb: &'c Reader<A, L>,
     key: &'k [u8],
     state: LookupState,
 }
 
-impl<'c, 'k, A> LookupIter<'c, 'k, A> {
-    fn new(cdb: &'c Reader<A>, key: &'k [u8]) -> Self {
+impl<'c, 'k, A, L: Layout> LookupIter<'c, 'k, A, L> {
+    fn new(cdb: &'c Reader<A, L>, key: &'k [u8]) -> Self {
+        Self::new_hashed(cdb, cdb.layout.hash(key), key)
+    }
+
+    fn new_hashed(cdb: &'c Reader<A, L>, hash: Hash, key: &'k [u8]) -> Self {
         LookupIter {
             cdb,
             key,
-            state: LookupState::new(cdb, key),
+            state: LookupState::new_hashed(cdb, hash),
         }
     }
 }
 
-impl<'c, 'k, A: CDBAccess> Iterator for LookupIter<'c, 'k, A> {
-    type Item = CDBResult<&'c [u8]>;
+impl<'c, 'k, A: CDBAccess, L: Layout> Iterator for LookupIter<'c, 'k, A, L> {
+    type Item = CDBResult<A::Data<'c>>;
 
     fn next(&mut self) -> Option<Self::Item> {
         self.state.next(self.cdb, self.key)
@@ -107,20 +359,29 @@ impl<'c, 'k, A: CDBAccess> Iterator for LookupIter<'c, 'k, A> {
 
 #[derive(Clone)]
 pub struct LookupState {
+    table: usize,
     table_pos: usize,
+    table_len: usize,
+    home: usize,
     khash: Hash,
     iter: Chain<Range<usize>, Range<usize>>,
+    robinhood: bool,
     done: bool,
 }
 
 impl LookupState {
     #[inline]
-    pub fn new<A>(cdb: &Reader<A>, key: &[u8]) -> Self {
-        let khash = Hash::new(key);
-        let table = cdb.tables[khash.table()];
+    pub fn new<A, L: Layout>(cdb: &Reader<A, L>, key: &[u8]) -> Self {
+        Self::new_hashed(cdb, cdb.layout.hash(key))
+    }
+
+    #[inline]
+    pub fn new_hashed<A, L: Layout>(cdb: &Reader<A, L>, khash: Hash) -> Self {
+        let tableidx = L::table_of(khash);
+        let table = cdb.tables()[tableidx];
 
         let start_pos = if table.len != 0 {
-            khash.slot(table.len)
+            L::slot_of(khash, table.len)
         } else {
             0
         };
@@ -129,23 +390,35 @@ impl LookupState {
         LookupState {
             khash,
             iter,
+            table: tableidx,
             table_pos: table.pos,
+            table_len: table.len,
+            home: start_pos,
+            robinhood: cdb.robinhood,
             done: false,
         }
     }
 
     #[inline]
-    pub fn next<'a, A: CDBAccess>(
+    pub fn next<'a, A: CDBAccess, L: Layout>(
+        &mut self,
+        cdb: &'a Reader<A, L>,
+        key: &[u8],
+    ) -> Option<CDBResult<A::Data<'a>>> {
+        self.next_record(cdb, key).map(|r| r.map(|(_, v)| v))
+    }
+
+    pub fn next_record<'a, A: CDBAccess, L: Layout>(
         &mut self,
-        cdb: &'a Reader<A>,
+        cdb: &'a Reader<A, L>,
         key: &[u8],
-    ) -> Option<CDBResult<&'a [u8]>> {
+    ) -> Option<CDBResult<(usize, A::Data<'a>)>> {
         if self.done {
             return None;
         }
 
-        while let Some(tableidx) = self.iter.next() {
-            let pos = self.table_pos + tableidx * PAIR_SIZE;
+        for slot in self.iter.by_ref() {
+            let pos = self.table_pos + slot * L::PAIR_SIZE;
 
             let (hash, ptr) = match cdb.read_hash_pos(pos) {
                 Ok(v) => v,
@@ -158,20 +431,37 @@ impl LookupState {
                 self.done = true;
                 return None;
             }
+            if ptr < L::HEADER_SIZE || ptr >= cdb.records_end() {
+                self.done = true;
+                return Some(Err(ReadError::HashPointerOutOfRange {
+                    table: self.table,
+                    slot,
+                    ptr,
+                }));
+            }
+
+            // In a robin-hood table no entry sits closer to its home slot
+            // than the key would, so the key cannot be further along.
+            if self.robinhood && hash != self.khash {
+                let len = self.table_len;
+                let probe = (slot + len - self.home) % len;
+                if (slot + len - L::slot_of(hash, len)) % len < probe {
+                    self.done = true;
+                    return None;
+                }
+            }
 
             if hash != self.khash {
                 continue;
             }
 
-            let (k, v, _) = match cdb.get_key_and_value(ptr) {
-                Ok(v) => v,
+            match cdb.read_value_if_key(ptr, key) {
+                Ok(Some(v)) => return Some(Ok((ptr, v))),
+                Ok(None) => (),
                 Err(e) => {
                     self.done = true;
                     return Some(Err(e));
                 }
-            };
-            if k == key {
-                return Some(Ok(v));
             }
         }
         self.done = true;
@@ -179,101 +469,401 @@ impl LookupState {
     }
 }
 
-type KeyValueNext<'c> = (&'c [u8], &'c [u8], usize);
+fn parse_header<L: Layout>(header: &[u8], datalen: usize) -> CDBResult<L::Tables> {
+    let mut tables = L::empty_tables();
+    let mut header_chunks = header.chunks_exact(L::WORD_SIZE);
+    let mut empty = true;
+
+    for (i, table) in tables.as_mut().iter_mut().enumerate() {
+        *table = PosLen {
+            pos: word_to_usize(L::read_word(header_chunks.next().unwrap())),
+            len: word_to_usize(L::read_word(header_chunks.next().unwrap())),
+        };
+
+        if !table.valid(datalen, L::PAIR_SIZE) {
+            return Err(ReadError::TableOutOfRange {
+                table: i,
+                pos: table.pos,
+                len: table.len,
+            });
+        }
+        empty &= table.len == 0;
+    }
+
+    // A database without records has every table right after the header.
+    if empty && tables.as_ref().iter().any(|t| t.pos != L::HEADER_SIZE) {
+        Err(ReadError::EmptyHeader)
+    } else {
+        Ok(tables)
+    }
+}
+
+#[inline]
+fn word_to_usize(word: u64) -> usize {
+    usize::try_from(word).unwrap_or(usize::MAX)
+}
+
+type KeyValueNext<'c, A> = (
+    <A as CDBAccess>::Data<'c>,
+    <A as CDBAccess>::Data<'c>,
+    usize,
+);
 
 impl<A: CDBAccess> Reader<A> {
     pub fn new(access: A) -> CDBResult<Reader<A>> {
-        let tables = Self::read_header(&access)?;
-        Ok(Reader { access, tables })
+        Self::with_layout(access)
+    }
+}
+
+impl<A, L: Layout> Reader<A, L> {
+    pub fn layout(&self) -> &L {
+        &self.layout
     }
 
-    fn get_key_and_value(&self, pos: usize) -> CDBResult<KeyValueNext<'_>> {
+    pub(crate) fn from_parts(access: A, layout: L, tables: L::Tables) -> Self {
+        Reader {
+            access,
+            layout,
+            tables,
+            robinhood: false,
+        }
+    }
+
+    pub fn is_robinhood(&self) -> bool {
+        self.robinhood
+    }
+
+    pub(crate) fn tables(&self) -> &[PosLen] {
+        self.tables.as_ref()
+    }
+
+    pub(crate) fn records_end(&self) -> usize {
+        self.tables()[0].pos
+    }
+}
+
+impl<A: CDBAccess, L: Layout> Reader<A, L> {
+    pub fn with_layout(access: A) -> CDBResult<Reader<A, L>> {
+        let (layout, tables) = Self::read_header(&access)?;
+        let robinhood = Self::read_robinhood(&access, tables.as_ref());
+        Ok(Reader {
+            access,
+            layout,
+            tables,
+            robinhood,
+        })
+    }
+
+    pub(crate) fn get_key_and_value(&self, pos: usize) -> CDBResult<KeyValueNext<'_, A>> {
         let (klen, vlen) = self.read_value_length(pos)?;
 
-        let keystart = pos + PAIR_SIZE;
-        let keyend = keystart + klen;
-        let valend = keyend + vlen;
+        let keystart = pos + L::PAIR_SIZE;
+        let (keyend, valend) = keystart
+            .checked_add(klen)
+            .and_then(|keyend| Some((keyend, keyend.checked_add(vlen)?)))
+            .ok_or(ReadError::RecordOverflow {
+                record: pos,
+                klen,
+                vlen,
+            })?;
 
         Ok((
-            Self::get_data(&self.access, keystart, klen)?,
-            Self::get_data(&self.access, keyend, vlen)?,
+            self.get_data(keystart, klen)?,
+            self.get_data(keyend, vlen)?,
             valend,
         ))
     }
 
-    pub fn iter(&self) -> impl Iterator<Item = CDBResult<(&'_ [u8], &'_ [u8])>> {
+    // Whether the empty-key record at `pos` could be the filler an aligned
+    // writer puts before the record at `next`.
+    pub(crate) fn padding_shape(&self, pos: usize, next: usize) -> CDBResult<bool> {
+        if next >= self.records_end() {
+            return Ok(false);
+        }
+        let (klen, _) = self.read_value_length(next)?;
+        Ok(next
+            .checked_add(L::PAIR_SIZE)
+            .and_then(|value| value.checked_add(klen))
+            .is_some_and(|value| filler_shape::<L>(pos, next, value)))
+    }
+
+    pub(crate) fn is_indexed_empty(&self, pos: usize) -> CDBResult<bool> {
+        for res in self.lookup_with_offsets(b"") {
+            if res?.0 == pos {
+                return Ok(true);
+            }
+        }
+        Ok(false)
+    }
+
+    pub(crate) fn read_value_if_key(
+        &self,
+        pos: usize,
+        key: &[u8],
+    ) -> CDBResult<Option<A::Data<'_>>> {
+        let (klen, vlen) = self.read_value_length(pos)?;
+        if klen != key.len() {
+            return Ok(None);
+        }
+
+        let keystart = pos + L::PAIR_SIZE;
+        if self.get_data(keystart, klen)?.as_ref() != key {
+            return Ok(None);
+        }
+
+        let keyend = keystart + klen;
+        if keyend.checked_add(vlen).is_none() {
+            return Err(ReadError::RecordOverflow {
+                record: pos,
+                klen,
+                vlen,
+            });
+        }
+
+        Ok(Some(self.get_data(keyend, vlen)?))
+    }
+
+    pub fn iter(&self) -> FileIter<'_, A, L> {
         FileIter::new(self)
     }
 
+    pub fn iter_from(&self, cursor: Cursor) -> CDBResult<FileIter<'_, A, L>> {
+        let bad = ReadError::BadCursor { token: cursor.0 };
+        let pos = match usize::try_from(cursor.0) {
+            Ok(pos) => pos,
+            Err(_) => return Err(bad),
+        };
+        let end = self.records_end();
+
+        // Besides the two ends of the record region, a cursor may only point
+        // at an indexed record, or at padding directly followed by one.
+        let valid = pos == 0
+            || pos == end
+            || match self.boundary_record(pos)? {
+                Some((true, _)) => true,
+                Some((false, next)) => {
+                    next == end || matches!(self.boundary_record(next)?, Some((true, _)))
+                }
+                None => false,
+            };
+        if !valid {
+            return Err(bad);
+        }
+
+        Ok(FileIter::starting_at(self, pos))
+    }
+
+    // Reads the record at `pos`. Returns whether a hash table points at
+    // it, and where the next record starts.
+    fn boundary_record(&self, pos: usize) -> CDBResult<Option<(bool, usize)>> {
+        let end = self.records_end();
+        if pos < L::HEADER_SIZE || pos >= end {
+            return Ok(None);
+        }
+        let (k, _, next) = match self.get_key_and_value(pos) {
+            Ok(record) => record,
+            #[cfg(feature = "std")]
+            Err(ReadError::Io(e)) => return Err(ReadError::Io(e)),
+            Err(_) => return Ok(None),
+        };
+        if next > end {
+            return Ok(None);
+        }
+        for offset in self.lookup_with_offsets(k.as_ref()) {
+            if offset?.0 == pos {
+                return Ok(Some((true, next)));
+            }
+        }
+        Ok(if k.as_ref().is_empty() && self.padding_shape(pos, next)? {
+            Some((false, next))
+        } else {
+            None
+        })
+    }
+
     pub fn lookup<'k, 'c: 'k>(
         &'c self,
         key: &'k [u8],
-    ) -> impl Iterator<Item = CDBResult<&'c [u8]>> + 'k {
+    ) -> impl Iterator<Item = CDBResult<A::Data<'c>>> + 'k {
         LookupIter::new(self, key)
     }
 
-    pub fn get<'c, 'k>(&'c self, key: &'k [u8]) -> CDBResult<Option<&'c [u8]>> {
+    pub fn get<'c>(&'c self, key: &[u8]) -> CDBResult<Option<A::Data<'c>>> {
         self.lookup(key).next().transpose()
     }
 
+    // `hash` must come from `self.layout().hash(key)`: Keyed files hash
+    // with their own seed, so a hash only carries over between readers
+    // whose layouts hash alike.
+    pub fn lookup_hashed<'k, 'c: 'k>(
+        &'c self,
+        hash: Hash,
+        key: &'k [u8],
+    ) -> impl Iterator<Item = CDBResult<A::Data<'c>>> + 'k {
+        debug_assert!(hash == self.layout.hash(key), "hash not from this layout");
+        LookupIter::new_hashed(self, hash, key)
+    }
+
+    pub fn get_hashed<'c>(&'c self, hash: Hash, key: &[u8]) -> CDBResult<Option<A::Data<'c>>> {
+        self.lookup_hashed(hash, key).next().transpose()
+    }
+
+    pub fn iter_with_offsets(&self) -> impl Iterator<Item = CDBResult<PosKeyValue<'_, A>>> {
+        let mut state = IterState::default();
+        let mut empty = EmptyKeys::default();
+        core::iter::from_fn(move || {
+            state.next_unpadded(self, self.records_end(), |pos| empty.indexed(self, pos))
+        })
+    }
+
+    pub fn lookup_with_offsets<'k, 'c: 'k>(
+        &'c self,
+        key: &'k [u8],
+    ) -> impl Iterator<Item = CDBResult<(usize, A::Data<'c>)>> + 'k {
+        let mut state = LookupState::new(self, key);
+        core::iter::from_fn(move || state.next_record(self, key))
+    }
+
+    pub fn get_with_offset<'c>(&'c self, key: &[u8]) -> CDBResult<Option<(usize, A::Data<'c>)>> {
+        self.lookup_with_offsets(key).next().transpose()
+    }
+
+    pub fn record_at(&self, offset: usize) -> CDBResult<KeyValue<'_, A>> {
+        let end = self.records_end();
+        if offset < L::HEADER_SIZE || offset >= end {
+            return Err(ReadError::OutOfBounds {
+                pos: offset,
+                len: L::PAIR_SIZE,
+            });
+        }
+
+        let (k, v, newpos) = self.get_key_and_value(offset)?;
+        if newpos > end {
+            return Err(ReadError::RecordPastEnd {
+                record: offset,
+                end,
+            });
+        }
+        Ok((k, v))
+    }
+
+    pub fn header(&self) -> impl ExactSizeIterator<Item = PosLen> + '_ {
+        self.tables().iter().copied()
+    }
+
+    fn table(&self, table: usize) -> CDBResult<PosLen> {
+        self.tables()
+            .get(table)
+            .copied()
+            .ok_or(ReadError::NoSuchTable {
+                table,
+                tables: L::ENTRIES,
+            })
+    }
+
+    pub fn table_slots(&self, table: usize) -> CDBResult<TableSlots<'_, A, L>> {
+        let table = self.table(table)?;
+        Ok(TableSlots {
+            cdb: self,
+            table,
+            slots: 0..table.len,
+        })
+    }
+
+    pub fn read_slot(&self, table: usize, slot: usize) -> CDBResult<(Hash, usize)> {
+        let table = self.table(table)?;
+        if slot >= table.len {
+            return Err(ReadError::OutOfBounds {
+                pos: table.pos + slot * L::PAIR_SIZE,
+                len: L::PAIR_SIZE,
+            });
+        }
+        self.read_hash_pos(table.pos + slot * L::PAIR_SIZE)
+    }
+
+    pub fn read_record(&self, pos: usize) -> CDBResult<KeyValueNext<'_, A>> {
+        self.get_key_and_value(pos)
+    }
+
+    pub fn access(&self) -> &A {
+        &self.access
+    }
+
+    pub fn into_access(self) -> A {
+        self.access
+    }
+
     #[inline]
-    fn read_pair(&self, pos: usize) -> CDBResult<(u32, u32)> {
-        let data = Self::get_data(&self.access, pos, PAIR_SIZE)?;
-        Ok((
-            u32::from_le_bytes(data[0..4].try_into().unwrap()),
-            u32::from_le_bytes(data[4..8].try_into().unwrap()),
-        ))
+    pub(crate) fn read_pair(&self, pos: usize) -> CDBResult<(u64, u64)> {
+        let mut data = [0u8; MAX_PAIR_SIZE];
+        let data = &mut data[..L::PAIR_SIZE];
+        self.access.read_exact_at(pos, data)?;
+        let (a, b) = data.split_at(L::WORD_SIZE);
+        Ok((L::read_word(a), L::read_word(b)))
     }
 
-    fn read_hash_pos(&self, pos: usize) -> CDBResult<(Hash, usize)> {
+    pub(crate) fn read_hash_pos(&self, pos: usize) -> CDBResult<(Hash, usize)> {
         let (hash, pos) = self.read_pair(pos)?;
-        Ok((Hash(hash), pos as usize))
+        Ok((Hash(hash as u32), word_to_usize(pos)))
     }
 
     fn read_value_length(&self, pos: usize) -> CDBResult<(usize, usize)> {
         let (klen, vlen) = self.read_pair(pos)?;
-        Ok((klen as usize, vlen as usize))
+        Ok((word_to_usize(klen), word_to_usize(vlen)))
     }
 
-    fn read_header(access: &A) -> CDBResult<[PosLen; ENTRIES]> {
-        let mut tables: [PosLen; ENTRIES] = [PosLen { pos: 0, len: 0 }; ENTRIES];
-        let header = Self::get_data(&access, 0, PAIR_SIZE * ENTRIES)?;
-        let mut header_chunks = header.chunks_exact(core::mem::size_of::<u32>());
-        let mut empty = true;
-
-        for table in tables.iter_mut() {
-            *table = PosLen {
-                pos: u32::from_le_bytes(header_chunks.next().unwrap().try_into().unwrap()) as usize,
-                len: u32::from_le_bytes(header_chunks.next().unwrap().try_into().unwrap()) as usize,
-            };
+    fn read_header(access: &A) -> CDBResult<(L, L::Tables)> {
+        let header = access.read_at(0, header_len::<L>(access.len()))?;
+        let header = header.as_ref();
+        let layout = L::from_header(header, access.len())?;
+        let tables = parse_header::<L>(&header[..L::ENTRIES * L::PAIR_SIZE], access.len())?;
+        Ok((layout, tables))
+    }
 
-            if !table.valid(access.as_ref().len()) {
-                return Err(ReadError::InvalidFile);
-            }
-            empty &= table.len == 0;
+    fn read_robinhood(access: &A, tables: &[PosLen]) -> bool {
+        let end = tables
+            .iter()
+            .map(|t| t.pos + t.len * L::PAIR_SIZE)
+            .max()
+            .unwrap_or(0);
+        match access.read_at(end, ROBINHOOD_MAGIC.len()) {
+            Ok(data) => data.as_ref() == ROBINHOOD_MAGIC,
+            Err(_) => false,
         }
+    }
 
-        if empty {
-            Err(ReadError::InvalidFile)
-        } else {
-            Ok(tables)
-        }
+    #[inline]
+    fn get_data(&self, pos: usize, len: usize) -> CDBResult<A::Data<'_>> {
+        self.access.read_at(pos, len)
     }
+}
+
+pub struct TableSlots<'a, A, L: Layout = Classic> {
+    cdb: &'a Reader<A, L>,
+    table: PosLen,
+    slots: Range<usize>,
+}
+
+impl<'a, A: CDBAccess, L: Layout> Iterator for TableSlots<'a, A, L> {
+    type Item = CDBResult<(Hash, usize)>;
 
-    fn get_data(access: &A, pos: usize, len: usize) -> CDBResult<&[u8]> {
-        let res = access
-            .as_ref()
-            .get(pos..pos + len)
-            .ok_or(ReadError::OutOfBounds)?;
-        Ok(res)
+    fn next(&mut self) -> Option<Self::Item> {
+        let slot = self.slots.next()?;
+        Some(self.cdb.read_hash_pos(self.table.pos + slot * L::PAIR_SIZE))
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        self.slots.size_hint()
     }
 }
 
-impl<'a, A: CDBAccess> IntoIterator for &'a Reader<A> {
-    type IntoIter = FileIter<'a, A>;
-    type Item = <FileIter<'a, A> as Iterator>::Item;
+impl<'a, A: CDBAccess, L: Layout> ExactSizeIterator for TableSlots<'a, A, L> {}
+
+impl<'a, A: CDBAccess, L: Layout> IntoIterator for &'a Reader<A, L> {
+    type IntoIter = FileIter<'a, A, L>;
+    type Item = <FileIter<'a, A, L> as Iterator>::Item;
 
-    fn into_iter(self) -> FileIter<'a, A> {
+    fn into_iter(self) -> FileIter<'a, A, L> {
         FileIter::new(self)
     }
 }
@@ -281,23 +871,259 @@ impl<'a, A: CDBAccess> IntoIterator for &'a Reader<A> {
 #[cfg(feature = "std")]
 impl std::convert::From<ReadError> for std::io::Error {
     fn from(error: ReadError) -> Self {
-        match error {
-            ReadError::OutOfBounds => std::io::ErrorKind::UnexpectedEof,
-            ReadError::InvalidFile => std::io::ErrorKind::InvalidData,
-        }
-        .into()
+        let kind = match error {
+            ReadError::Io(e) => return e,
+            ReadError::OutOfBounds { .. } => std::io::ErrorKind::UnexpectedEof,
+            _ => std::io::ErrorKind::InvalidData,
+        };
+        std::io::Error::new(kind, error)
     }
 }
 
 #[cfg(feature = "std")]
-impl std::fmt::Display for ReadError {
-    fn fmt(&self, fmt: &mut std::fmt::Formatter<'_>) -> std::result::Result<(), std::fmt::Error> {
+impl std::convert::From<std::io::Error> for ReadError {
+    fn from(error: std::io::Error) -> Self {
+        ReadError::Io(error)
+    }
+}
+
+impl core::fmt::Display for ReadError {
+    fn fmt(&self, fmt: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
         match self {
-            ReadError::OutOfBounds => write!(fmt, "Index out of bounds"),
-            ReadError::InvalidFile => write!(fmt, "Invalid CDB file"),
+            ReadError::OutOfBounds { pos, len } => {
+                write!(
+                    fmt,
+                    "read of {} bytes at offset {} is out of bounds",
+                    len, pos
+                )
+            }
+            ReadError::TableOutOfRange { table, pos, len } => write!(
+                fmt,
+                "header entry {} (offset {}, {} slots) points outside the file",
+                table, pos, len
+            ),
+            ReadError::EmptyHeader => write!(fmt, "all header entries are empty"),
+            ReadError::BadMagic => write!(fmt, "header magic does not match the file layout"),
+            ReadError::PodMismatch {
+                pos,
+                len,
+                size,
+                align,
+            } => write!(
+                fmt,
+                "value at offset {} ({} bytes) cannot be read as a {}-byte type aligned to {}",
+                pos, len, size, align
+            ),
+            ReadError::RecordOverflow { record, klen, vlen } => write!(
+                fmt,
+                "record at offset {}: key length {} and value length {} overflow",
+                record, klen, vlen
+            ),
+            ReadError::RecordPastEnd { record, end } => write!(
+                fmt,
+                "record at offset {} extends past the end of the record region at {}",
+                record, end
+            ),
+            ReadError::HashPointerOutOfRange { table, slot, ptr } => write!(
+                fmt,
+                "table {} slot {} points at offset {}, outside the record region",
+                table, slot, ptr
+            ),
+            ReadError::BadCursor { token } => {
+                write!(fmt, "cursor {} is not at a record boundary", token)
+            }
+            ReadError::NoSuchTable { table, tables } => {
+                write!(fmt, "there is no table {}, the file has {}", table, tables)
+            }
+            #[cfg(feature = "std")]
+            ReadError::Io(e) => write!(fmt, "I/O error: {}", e),
         }
     }
 }
-
 #[cfg(feature = "std")]
-impl std::error::Error for ReadError {}
+impl std::error::Error for ReadError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            ReadError::Io(e) => Some(e),
+            _ => None,
+        }
+    }
+}
+
+#[cfg(all(test, feature = "alloc"))]
+pub(crate) mod tests {
+    use super::*;
+    use crate::MemWriter;
+    use alloc::vec::Vec;
+
+    pub(crate) const KEYS: u32 = 1000;
+
+    pub(crate) fn fill<L: Layout>(writer: &mut MemWriter<Vec<u8>, L>, dups: bool) {
+        for i in 0..KEYS {
+            writer
+                .write(&i.to_le_bytes(), &(i * 3).to_le_bytes())
+                .unwrap();
+        }
+        if dups {
+            for v in [b"1", b"2", b"3"] {
+                writer.write(b"dup", v).unwrap();
+            }
+        }
+    }
+
+    pub(crate) fn check<L: Layout>(data: &[u8]) {
+        let cdb = Reader::<_, L>::with_layout(data).unwrap();
+
+        for i in 0..KEYS {
+            assert_eq!(
+                cdb.get(&i.to_le_bytes()).unwrap(),
+                Some(&(i * 3).to_le_bytes()[..])
+            );
+        }
+        for i in KEYS..KEYS * 4 {
+            assert_eq!(cdb.get(&i.to_le_bytes()).unwrap(), None);
+        }
+        assert_eq!(cdb.get(b"").unwrap(), None);
+
+        let dups: Vec<&[u8]> = cdb.lookup(b"dup").map(Result::unwrap).collect();
+        assert_eq!(dups, [b"1", b"2", b"3"]);
+        assert_eq!(cdb.iter().count(), KEYS as usize + 3);
+    }
+
+    #[test]
+    fn robinhood() {
+        let mut writer = MemWriter::new(Vec::new()).unwrap();
+        fill(&mut writer, true);
+        let data = writer.finish_robinhood().unwrap();
+
+        assert!(data.ends_with(ROBINHOOD_MAGIC));
+        assert!(Reader::new(&data[..]).unwrap().is_robinhood());
+        check::<Classic>(&data);
+    }
+
+    #[test]
+    fn naive_without_marker() {
+        let mut writer = MemWriter::new(Vec::new()).unwrap();
+        fill(&mut writer, true);
+        let data = writer.finish_naive().unwrap();
+
+        assert!(!data.ends_with(ROBINHOOD_MAGIC));
+        assert!(!Reader::new(&data[..]).unwrap().is_robinhood());
+        check::<Classic>(&data);
+    }
+
+    fn aligned() -> Vec<u8> {
+        let mut writer = MemWriter::new(Vec::new()).unwrap();
+        writer.set_alignment(16);
+        for i in 0..50u32 {
+            let value = alloc::vec![1; i as usize % 7];
+            writer.write(&i.to_le_bytes(), &value).unwrap();
+        }
+        writer.finish().unwrap()
+    }
+
+    fn keys<A: CDBAccess, L: Layout>(iter: FileIter<'_, A, L>) -> Vec<Vec<u8>> {
+        iter.map(|r| r.unwrap().0.as_ref().to_vec()).collect()
+    }
+
+    #[test]
+    fn cursor_resumes_everywhere() {
+        let data = aligned();
+        let cdb = Reader::new(&data[..]).unwrap();
+        let all = keys(cdb.iter());
+        assert_eq!(all.len(), 50);
+
+        // Cursors land on records and on the padding in front of them.
+        let mut iter = cdb.iter();
+        let mut padded = 0;
+        for i in 0..=all.len() {
+            let cursor = iter.cursor();
+            let pos = cursor.token() as usize;
+            if pos != 0 && pos != cdb.records_end() {
+                let (k, _, _) = cdb.get_key_and_value(pos).unwrap();
+                padded += k.as_ref().is_empty() as usize;
+            }
+            assert_eq!(keys(cdb.iter_from(cursor).unwrap()), all[i..]);
+            iter.next();
+        }
+        assert!(padded > 0);
+        assert_eq!(iter.cursor().token() as usize, cdb.records_end());
+    }
+
+    #[test]
+    fn cursor_inside_record() {
+        let data = aligned();
+        let cdb = Reader::new(&data[..]).unwrap();
+
+        for (pos, _, _) in cdb.iter_with_offsets().map(Result::unwrap) {
+            for token in [pos + 1, pos + 4] {
+                assert!(matches!(
+                    cdb.iter_from(Cursor::from_token(token as u64)),
+                    Err(ReadError::BadCursor { .. })
+                ));
+            }
+        }
+        for token in [1, cdb.records_end() as u64 + 1, u64::MAX] {
+            assert!(matches!(
+                cdb.iter_from(Cursor::from_token(token)),
+                Err(ReadError::BadCursor { .. })
+            ));
+        }
+    }
+
+    // Fails the first read at one offset.
+    struct FailOnce {
+        data: Vec<u8>,
+        fail_at: core::cell::Cell<Option<usize>>,
+    }
+
+    impl CDBAccess for FailOnce {
+        type Data<'a> = &'a [u8];
+
+        fn len(&self) -> usize {
+            self.data.len()
+        }
+
+        fn read_at(&self, pos: usize, len: usize) -> CDBResult<&[u8]> {
+            if self.fail_at.get() == Some(pos) {
+                self.fail_at.set(None);
+                return Err(ReadError::OutOfBounds { pos, len });
+            }
+            self.data.read_at(pos, len)
+        }
+    }
+
+    #[test]
+    fn cursor_after_error() {
+        let data = aligned();
+        let all = keys(Reader::new(&data[..]).unwrap().iter());
+        let third = Reader::new(&data[..])
+            .unwrap()
+            .iter_with_offsets()
+            .nth(2)
+            .unwrap()
+            .unwrap()
+            .0;
+
+        let cdb = Reader::new(FailOnce {
+            data,
+            fail_at: core::cell::Cell::new(None),
+        })
+        .unwrap();
+        cdb.access().fail_at.set(Some(third));
+
+        let mut iter = cdb.iter();
+        let mut seen = Vec::new();
+        for r in iter.by_ref() {
+            match r {
+                Ok((k, _)) => seen.push(k.to_vec()),
+                Err(_) => break,
+            }
+        }
+        assert_eq!(seen.len(), 2);
+        assert!(iter.cursor().token() as usize <= third);
+
+        seen.extend(keys(cdb.iter_from(iter.cursor()).unwrap()));
+        assert_eq!(seen, all);
+    }
+}
@@ -0,0 +1,99 @@
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, Mutex, RwLock, TryLockError};
+use std::time::{Duration, Instant};
+
+use super::*;
+
+pub struct ReloadingReader<L: Layout = Classic> {
+    path: PathBuf,
+    options: MapOptions,
+    interval: Option<Duration>,
+    checked: Mutex<Instant>,
+    current: RwLock<Arc<Reader<MappedFile, L>>>,
+}
+
+impl<L: Layout> std::fmt::Debug for ReloadingReader<L> {
+    fn fmt(&self, fmt: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        fmt.debug_struct("ReloadingReader")
+            .field("path", &self.path)
+            .field("interval", &self.interval)
+            .finish()
+    }
+}
+
+impl ReloadingReader {
+    pub fn open<P: AsRef<Path>>(path: P, options: &MapOptions) -> Result<Self, ReadError> {
+        Self::with_layout(path, options)
+    }
+}
+
+impl<L: Layout> ReloadingReader<L> {
+    pub fn with_layout<P: AsRef<Path>>(path: P, options: &MapOptions) -> Result<Self, ReadError> {
+        let path = path.as_ref().to_path_buf();
+        let reader = Reader::with_layout(MappedFile::open(&path, options)?)?;
+
+        Ok(ReloadingReader {
+            path,
+            options: options.clone(),
+            interval: None,
+            checked: Mutex::new(Instant::now()),
+            current: RwLock::new(Arc::new(reader)),
+        })
+    }
+
+    pub fn set_interval(&mut self, interval: Option<Duration>) {
+        self.interval = interval;
+    }
+
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+
+    // Returns the current reader, checking the file first when the
+    // interval has elapsed. A file that fails to open or parse leaves the
+    // previous reader in place; `reload` reports the error.
+    pub fn reader(&self) -> Arc<Reader<MappedFile, L>> {
+        if let Some(interval) = self.interval {
+            // Another thread already checking is as good as checking.
+            let checked = match self.checked.try_lock() {
+                Ok(checked) => Some(checked),
+                Err(TryLockError::Poisoned(e)) => Some(e.into_inner()),
+                Err(TryLockError::WouldBlock) => None,
+            };
+            if let Some(mut checked) = checked {
+                if checked.elapsed() >= interval {
+                    *checked = Instant::now();
+                    let _ = self.reload_locked();
+                }
+            }
+        }
+        self.current()
+    }
+
+    pub fn reload(&self) -> Result<bool, ReadError> {
+        let mut checked = self.checked.lock().unwrap_or_else(|e| e.into_inner());
+        *checked = Instant::now();
+        self.reload_locked()
+    }
+
+    fn current(&self) -> Arc<Reader<MappedFile, L>> {
+        self.current
+            .read()
+            .unwrap_or_else(|e| e.into_inner())
+            .clone()
+    }
+
+    fn reload_locked(&self) -> Result<bool, ReadError> {
+        if self.current().access().status()? == FileStatus::Unchanged {
+            return Ok(false);
+        }
+
+        let reader = Reader::with_layout(MappedFile::open(&self.path, &self.options)?)?;
+        *self.current.write().unwrap_or_else(|e| e.into_inner()) = Arc::new(reader);
+        Ok(true)
+    }
+
+    pub fn get(&self, key: &[u8]) -> Result<Option<Value<MappedFile, L>>, ReadError> {
+        SharedReader::from(self.reader()).get(key)
+    }
+}
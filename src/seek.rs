@@ -0,0 +1,75 @@
+use std::cell::RefCell;
+use std::convert::TryFrom;
+use std::io::{Read, Seek, SeekFrom};
+
+use super::*;
+
+pub type SeekReader<R, L = Classic> = Reader<SeekAccess<R>, L>;
+
+struct SeekState<R> {
+    inner: R,
+    pos: Option<u64>,
+}
+
+pub struct SeekAccess<R> {
+    state: RefCell<SeekState<R>>,
+    len: usize,
+}
+
+impl<R> std::fmt::Debug for SeekAccess<R> {
+    fn fmt(&self, fmt: &mut std::fmt::Formatter<'_>) -> std::result::Result<(), std::fmt::Error> {
+        write!(fmt, "SeekAccess {{ len: {} }}", self.len)
+    }
+}
+
+impl<R: Read + Seek> SeekAccess<R> {
+    pub fn new(mut inner: R) -> std::io::Result<Self> {
+        let len = inner.seek(SeekFrom::End(0))?;
+        let len = usize::try_from(len).unwrap_or(usize::MAX);
+
+        Ok(SeekAccess {
+            state: RefCell::new(SeekState { inner, pos: None }),
+            len,
+        })
+    }
+
+    pub fn into_inner(self) -> R {
+        self.state.into_inner().inner
+    }
+}
+
+impl<R: Read + Seek> CDBAccess for SeekAccess<R> {
+    type Data<'a>
+        = Vec<u8>
+    where
+        R: 'a;
+
+    fn len(&self) -> usize {
+        self.len
+    }
+
+    fn read_at(&self, pos: usize, len: usize) -> Result<Vec<u8>, ReadError> {
+        check_bounds(self, pos, len)?;
+        let mut data = vec![0u8; len];
+        self.read_exact_at(pos, &mut data)?;
+        Ok(data)
+    }
+
+    fn read_exact_at(&self, pos: usize, buf: &mut [u8]) -> Result<(), ReadError> {
+        check_bounds(self, pos, buf.len())?;
+
+        let mut state = self.state.borrow_mut();
+        let pos = pos as u64;
+
+        // Forget the position until the read completes: a failed read
+        // leaves the stream somewhere between `pos` and the end.
+        let cached = state.pos.take();
+        if cached != Some(pos) {
+            state.inner.seek(SeekFrom::Start(pos))?;
+        }
+        state.inner.read_exact(buf)?;
+        state.pos = Some(pos + buf.len() as u64);
+
+        Ok(())
+    }
+}
@@ -0,0 +1,201 @@
+use alloc::vec::Vec;
+
+use super::siphash::siphash24;
+use super::*;
+
+#[cfg(feature = "std")]
+const MANIFEST_MAGIC: &str = "cordoba-shards 1";
+
+// Keys are routed with their own keyed hash so that the keys of one shard
+// still spread evenly over that shard's tables.
+pub fn shard_of(key: &[u8], shards: usize) -> usize {
+    assert!(shards > 0, "a sharded set needs at least one shard");
+    (siphash24(0x7368_6172_6473_2d6b, 0x6579_2d72_6f75_7465, key) % shards as u64) as usize
+}
+
+pub struct ShardedReader<A, L: Layout = Classic> {
+    shards: Vec<Reader<A, L>>,
+}
+
+impl<A, L: Layout> core::fmt::Debug for ShardedReader<A, L> {
+    fn fmt(&self, fmt: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        write!(fmt, "ShardedReader {{ shards: {} }}", self.shards.len())
+    }
+}
+
+impl<A, L: Layout> From<Vec<Reader<A, L>>> for ShardedReader<A, L> {
+    fn from(shards: Vec<Reader<A, L>>) -> Self {
+        Self::new(shards)
+    }
+}
+
+impl<A, L: Layout> ShardedReader<A, L> {
+    pub fn new(shards: Vec<Reader<A, L>>) -> Self {
+        assert!(!shards.is_empty(), "a sharded set needs at least one shard");
+        ShardedReader { shards }
+    }
+
+    pub fn shards(&self) -> &[Reader<A, L>] {
+        &self.shards
+    }
+
+    pub fn shard(&self, key: &[u8]) -> &Reader<A, L> {
+        &self.shards[shard_of(key, self.shards.len())]
+    }
+}
+
+impl<A: CDBAccess, L: Layout> ShardedReader<A, L> {
+    pub fn get<'c>(&'c self, key: &[u8]) -> Result<Option<A::Data<'c>>, ReadError> {
+        self.shard(key).get(key)
+    }
+
+    pub fn lookup<'k, 'c: 'k>(
+        &'c self,
+        key: &'k [u8],
+    ) -> impl Iterator<Item = Result<A::Data<'c>, ReadError>> + 'k {
+        self.shard(key).lookup(key)
+    }
+
+    pub fn iter(&self) -> impl Iterator<Item = Result<(A::Data<'_>, A::Data<'_>), ReadError>> {
+        self.shards.iter().flat_map(|shard| shard.iter())
+    }
+}
+
+#[cfg(feature = "std")]
+fn shard_paths(manifest: &std::path::Path, shards: usize) -> Vec<std::path::PathBuf> {
+    let stem = manifest
+        .file_stem()
+        .map(|s| s.to_string_lossy().into_owned())
+        .unwrap_or_default();
+    (0..shards)
+        .map(|i| manifest.with_file_name(format!("{}.{}.cdb", stem, i)))
+        .collect()
+}
+
+// The manifest is a short text file: a magic line, the shard count, then
+// one shard file name per line, relative to the manifest's directory.
+#[cfg(feature = "mmap")]
+fn read_manifest(manifest: &std::path::Path) -> std::io::Result<Vec<std::path::PathBuf>> {
+    let invalid = |msg| std::io::Error::new(std::io::ErrorKind::InvalidData, msg);
+
+    let text = std::fs::read_to_string(manifest)?;
+    let mut lines = text.lines();
+    if lines.next() != Some(MANIFEST_MAGIC) {
+        return Err(invalid("not a shard manifest"));
+    }
+    let count: usize = lines
+        .next()
+        .and_then(|l| l.parse().ok())
+        .ok_or_else(|| invalid("missing shard count"))?;
+    let paths: Vec<_> = lines.map(|l| manifest.with_file_name(l)).collect();
+    if count == 0 || paths.len() != count {
+        return Err(invalid("shard count does not match the listed files"));
+    }
+    Ok(paths)
+}
+
+#[cfg(feature = "mmap")]
+impl ShardedReader<MappedFile> {
+    pub fn open<P: AsRef<std::path::Path>>(
+        manifest: P,
+        options: &MapOptions,
+    ) -> Result<Self, ReadError> {
+        Self::open_with_layout(manifest, options)
+    }
+}
+
+#[cfg(feature = "mmap")]
+impl<L: Layout> ShardedReader<MappedFile, L> {
+    pub fn open_with_layout<P: AsRef<std::path::Path>>(
+        manifest: P,
+        options: &MapOptions,
+    ) -> Result<Self, ReadError> {
+        let shards = read_manifest(manifest.as_ref())?
+            .iter()
+            .map(|path| Reader::with_layout(MappedFile::open(path, options)?))
+            .collect::<Result<_, ReadError>>()?;
+        Ok(ShardedReader { shards })
+    }
+}
+
+#[cfg(feature = "std")]
+pub struct ShardedWriter<L: Layout = Classic> {
+    manifest: std::path::PathBuf,
+    paths: Vec<std::path::PathBuf>,
+    writers: Vec<Writer<std::io::BufWriter<std::fs::File>, L>>,
+}
+
+#[cfg(feature = "std")]
+impl<L: Layout> core::fmt::Debug for ShardedWriter<L> {
+    fn fmt(&self, fmt: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        fmt.debug_struct("ShardedWriter")
+            .field("manifest", &self.manifest)
+            .field("shards", &self.paths)
+            .finish()
+    }
+}
+
+#[cfg(feature = "std")]
+impl ShardedWriter {
+    pub fn create<P: AsRef<std::path::Path>>(
+        manifest: P,
+        shards: usize,
+    ) -> Result<Self, std::io::Error> {
+        Self::with_layout(manifest, shards, Classic)
+    }
+}
+
+#[cfg(feature = "std")]
+impl<L: Layout> ShardedWriter<L> {
+    // Shard files are created next to the manifest as `<stem>.<n>.cdb`.
+    pub fn with_layout<P: AsRef<std::path::Path>>(
+        manifest: P,
+        shards: usize,
+        layout: L,
+    ) -> Result<Self, std::io::Error> {
+        assert!(shards > 0, "a sharded set needs at least one shard");
+        let manifest = manifest.as_ref().to_path_buf();
+        let paths = shard_paths(&manifest, shards);
+        let writers = paths
+            .iter()
+            .map(|path| {
+                let file = std::io::BufWriter::new(std::fs::File::create(path)?);
+                Writer::with_layout(file, layout.clone())
+            })
+            .collect::<Result<_, _>>()?;
+
+        Ok(ShardedWriter {
+            manifest,
+            paths,
+            writers,
+        })
+    }
+
+    pub fn set_alignment(&mut self, align: usize) {
+        for writer in &mut self.writers {
+            writer.set_alignment(align);
+        }
+    }
+
+    pub fn write(&mut self, k: &[u8], v: &[u8]) -> Result<(), std::io::Error> {
+        let shard = shard_of(k, self.writers.len());
+        self.writers[shard].write(k, v)
+    }
+
+    // The manifest is written last, so it only ever lists complete shards.
+    pub fn finish(self) -> Result<(), std::io::Error> {
+        use std::io::Write;
+
+        for writer in self.writers {
+            writer.finish()?.flush()?;
+        }
+
+        let mut text = format!("{}\n{}\n", MANIFEST_MAGIC, self.paths.len());
+        for path in &self.paths {
+            let name = path.file_name().unwrap_or_default();
+            text.push_str(&name.to_string_lossy());
+            text.push('\n');
+        }
+        std::fs::write(&self.manifest, text)
+    }
+}
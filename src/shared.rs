@@ -0,0 +1,186 @@
+use alloc::sync::Arc;
+use alloc::vec::Vec;
+use core::ops::Deref;
+
+use super::*;
+
+pub struct SharedReader<A, L: Layout = Classic> {
+    inner: Arc<Reader<A, L>>,
+}
+
+impl<A, L: Layout> Clone for SharedReader<A, L> {
+    fn clone(&self) -> Self {
+        SharedReader {
+            inner: self.inner.clone(),
+        }
+    }
+}
+
+impl<A, L: Layout> core::fmt::Debug for SharedReader<A, L> {
+    fn fmt(&self, fmt: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        write!(fmt, "SharedReader {{}}")
+    }
+}
+
+impl<A, L: Layout> From<Reader<A, L>> for SharedReader<A, L> {
+    fn from(reader: Reader<A, L>) -> Self {
+        SharedReader {
+            inner: Arc::new(reader),
+        }
+    }
+}
+
+impl<A, L: Layout> From<Arc<Reader<A, L>>> for SharedReader<A, L> {
+    fn from(inner: Arc<Reader<A, L>>) -> Self {
+        SharedReader { inner }
+    }
+}
+
+impl<A: AsRef<[u8]>> SharedReader<A> {
+    pub fn new(access: A) -> Result<Self, ReadError> {
+        Self::with_layout(access)
+    }
+}
+
+impl<A: AsRef<[u8]>, L: Layout> SharedReader<A, L> {
+    pub fn with_layout(access: A) -> Result<Self, ReadError> {
+        Ok(Reader::with_layout(access)?.into())
+    }
+
+    pub fn reader(&self) -> &Reader<A, L> {
+        &self.inner
+    }
+
+    pub fn into_inner(self) -> Arc<Reader<A, L>> {
+        self.inner
+    }
+
+    fn value(&self, data: &[u8]) -> Value<A, L> {
+        let base = self.inner.access().as_ref().as_ptr() as usize;
+        Value {
+            reader: self.inner.clone(),
+            pos: data.as_ptr() as usize - base,
+            len: data.len(),
+        }
+    }
+
+    pub fn get(&self, key: &[u8]) -> Result<Option<Value<A, L>>, ReadError> {
+        Ok(self.inner.get(key)?.map(|v| self.value(v)))
+    }
+
+    pub fn lookup(&self, key: &[u8]) -> SharedLookup<A, L> {
+        SharedLookup {
+            state: LookupState::new(&self.inner, key),
+            cdb: self.clone(),
+            key: key.to_vec(),
+        }
+    }
+
+    pub fn iter(&self) -> SharedIter<A, L> {
+        SharedIter {
+            cdb: self.clone(),
+            state: IterState::default(),
+            empty: EmptyKeys::default(),
+        }
+    }
+}
+
+pub struct Value<A, L: Layout = Classic> {
+    reader: Arc<Reader<A, L>>,
+    pos: usize,
+    len: usize,
+}
+
+impl<A, L: Layout> Clone for Value<A, L> {
+    fn clone(&self) -> Self {
+        Value {
+            reader: self.reader.clone(),
+            pos: self.pos,
+            len: self.len,
+        }
+    }
+}
+
+impl<A: AsRef<[u8]>, L: Layout> Value<A, L> {
+    pub fn offset(&self) -> usize {
+        self.pos
+    }
+}
+
+impl<A: AsRef<[u8]>, L: Layout> Deref for Value<A, L> {
+    type Target = [u8];
+
+    fn deref(&self) -> &[u8] {
+        &self.reader.access().as_ref()[self.pos..self.pos + self.len]
+    }
+}
+
+impl<A: AsRef<[u8]>, L: Layout> AsRef<[u8]> for Value<A, L> {
+    fn as_ref(&self) -> &[u8] {
+        self
+    }
+}
+
+impl<A: AsRef<[u8]>, L: Layout> core::fmt::Debug for Value<A, L> {
+    fn fmt(&self, fmt: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        write!(fmt, "Value({:?})", &**self)
+    }
+}
+
+impl<A: AsRef<[u8]>, L: Layout> PartialEq<[u8]> for Value<A, L> {
+    fn eq(&self, other: &[u8]) -> bool {
+        **self == *other
+    }
+}
+
+pub struct SharedLookup<A, L: Layout = Classic> {
+    cdb: SharedReader<A, L>,
+    key: Vec<u8>,
+    state: LookupState,
+}
+
+impl<A: AsRef<[u8]>, L: Layout> Iterator for SharedLookup<A, L> {
+    type Item = Result<Value<A, L>, ReadError>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        let value = self.state.next(&self.cdb.inner, &self.key)?;
+        Some(value.map(|v| self.cdb.value(v)))
+    }
+}
+
+pub struct SharedIter<A, L: Layout = Classic> {
+    cdb: SharedReader<A, L>,
+    state: IterState,
+    empty: EmptyKeys,
+}
+
+impl<A, L: Layout> Clone for SharedIter<A, L> {
+    fn clone(&self) -> Self {
+        SharedIter {
+            cdb: self.cdb.clone(),
+            state: self.state,
+            empty: self.empty.clone(),
+        }
+    }
+}
+
+impl<A: AsRef<[u8]>, L: Layout> Iterator for SharedIter<A, L> {
+    type Item = Result<(Value<A, L>, Value<A, L>), ReadError>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        let (cdb, empty) = (&self.cdb.inner, &mut self.empty);
+        let record = self
+            .state
+            .next_unpadded(cdb, cdb.records_end(), |pos| empty.indexed(cdb, pos))?;
+        Some(record.map(|(_, k, v)| (self.cdb.value(k), self.cdb.value(v))))
+    }
+}
+
+impl<A: AsRef<[u8]>, L: Layout> IntoIterator for &SharedReader<A, L> {
+    type IntoIter = SharedIter<A, L>;
+    type Item = <SharedIter<A, L> as Iterator>::Item;
+
+    fn into_iter(self) -> SharedIter<A, L> {
+        self.iter()
+    }
+}
@@ -0,0 +1,120 @@
+use alloc::vec::Vec;
+
+use super::*;
+
+#[derive(Debug, Clone, Copy, Default)]
+pub struct LengthStats {
+    pub min: usize,
+    pub max: usize,
+    pub total: u64,
+}
+
+impl LengthStats {
+    fn add(&mut self, count: usize, len: usize) {
+        if count == 0 || len < self.min {
+            self.min = len;
+        }
+        self.max = self.max.max(len);
+        self.total += len as u64;
+    }
+
+    pub fn avg(&self, count: usize) -> f64 {
+        if count == 0 {
+            0.0
+        } else {
+            self.total as f64 / count as f64
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, Default)]
+pub struct TableStats {
+    pub slots: usize,
+    pub used: usize,
+}
+
+impl TableStats {
+    pub fn load_factor(&self) -> f64 {
+        if self.slots == 0 {
+            0.0
+        } else {
+            self.used as f64 / self.slots as f64
+        }
+    }
+}
+
+#[derive(Debug, Clone, Default)]
+pub struct Stats {
+    pub records: usize,
+    pub distinct_keys: usize,
+    pub key_len: LengthStats,
+    pub value_len: LengthStats,
+    pub tables: Vec<TableStats>,
+    pub distances: Vec<usize>,
+}
+
+impl Stats {
+    pub fn slots(&self) -> usize {
+        self.tables.iter().map(|t| t.slots).sum()
+    }
+
+    pub fn used_slots(&self) -> usize {
+        self.tables.iter().map(|t| t.used).sum()
+    }
+
+    pub fn load_factor(&self) -> f64 {
+        let slots = self.slots();
+        if slots == 0 {
+            0.0
+        } else {
+            self.used_slots() as f64 / slots as f64
+        }
+    }
+}
+
+impl<A: CDBAccess, L: Layout> Reader<A, L> {
+    pub fn stats(&self) -> Result<Stats, ReadError> {
+        let mut stats = Stats::default();
+
+        for res in self.iter_with_offsets() {
+            let (pos, k, v) = res?;
+            let (k, v) = (k.as_ref(), v.as_ref());
+
+            stats.key_len.add(stats.records, k.len());
+            stats.value_len.add(stats.records, v.len());
+            stats.records += 1;
+
+            let mut state = LookupState::new(self, k);
+            if let Some(first) = state.next_record(self, k) {
+                if first?.0 == pos {
+                    stats.distinct_keys += 1;
+                }
+            }
+        }
+
+        for table in self.tables().iter() {
+            let mut tstats = TableStats {
+                slots: table.len,
+                used: 0,
+            };
+
+            for slot in 0..table.len {
+                let (hash, ptr) = self.read_hash_pos(table.pos + slot * L::PAIR_SIZE)?;
+                if ptr == 0 {
+                    continue;
+                }
+                tstats.used += 1;
+
+                let home = L::slot_of(hash, table.len);
+                let distance = (slot + table.len - home) % table.len;
+                if stats.distances.len() <= distance {
+                    stats.distances.resize(distance + 1, 0);
+                }
+                stats.distances[distance] += 1;
+            }
+            stats.tables.push(tstats);
+        }
+
+        Ok(stats)
+    }
+}
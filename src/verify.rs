@@ -0,0 +1,479 @@
+use alloc::vec::Vec;
+
+use super::*;
+
+#[derive(Debug)]
+#[non_exhaustive]
+pub enum VerifyProblem {
+    Read(ReadError),
+    RecordPastEnd {
+        record: usize,
+        end: usize,
+    },
+    TableOverlapsRecords {
+        table: usize,
+        pos: usize,
+    },
+    TablesOverlap {
+        table: usize,
+        other: usize,
+    },
+    SlotNotRecord {
+        table: usize,
+        slot: usize,
+        ptr: usize,
+    },
+    SlotHashMismatch {
+        table: usize,
+        slot: usize,
+        ptr: usize,
+        stored: Hash,
+        actual: Hash,
+    },
+    SlotWrongTable {
+        table: usize,
+        slot: usize,
+        ptr: usize,
+    },
+    SlotUnreachable {
+        table: usize,
+        slot: usize,
+        ptr: usize,
+    },
+    DuplicateSlot {
+        table: usize,
+        slot: usize,
+        ptr: usize,
+    },
+    UnindexedRecord {
+        record: usize,
+    },
+    RobinHoodOrder {
+        table: usize,
+        slot: usize,
+    },
+}
+
+impl core::fmt::Display for VerifyProblem {
+    fn fmt(&self, fmt: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        match self {
+            VerifyProblem::Read(e) => write!(fmt, "{}", e),
+            VerifyProblem::RecordPastEnd { record, end } => write!(
+                fmt,
+                "record at offset {} extends past the end of the record region at {}",
+                record, end
+            ),
+            VerifyProblem::TableOverlapsRecords { table, pos } => write!(
+                fmt,
+                "table {} at offset {} overlaps the record region",
+                table, pos
+            ),
+            VerifyProblem::TablesOverlap { table, other } => {
+                write!(fmt, "table {} overlaps table {}", table, other)
+            }
+            VerifyProblem::SlotNotRecord { table, slot, ptr } => write!(
+                fmt,
+                "table {} slot {} points at offset {}, which is not a record start",
+                table, slot, ptr
+            ),
+            VerifyProblem::SlotHashMismatch {
+                table,
+                slot,
+                ptr,
+                stored,
+                actual,
+            } => write!(
+                fmt,
+                "table {} slot {} stores {:?} for the record at offset {}, key hashes to {:?}",
+                table, slot, stored, ptr, actual
+            ),
+            VerifyProblem::SlotWrongTable { table, slot, ptr } => write!(
+                fmt,
+                "table {} slot {} points at offset {}, whose key belongs to another table",
+                table, slot, ptr
+            ),
+            VerifyProblem::SlotUnreachable { table, slot, ptr } => write!(
+                fmt,
+                "table {} slot {} points at offset {} but is cut off by an empty slot",
+                table, slot, ptr
+            ),
+            VerifyProblem::DuplicateSlot { table, slot, ptr } => write!(
+                fmt,
+                "table {} slot {} points at offset {}, which is already indexed",
+                table, slot, ptr
+            ),
+            VerifyProblem::UnindexedRecord { record } => write!(
+                fmt,
+                "record at offset {} is not reachable through the hash tables",
+                record
+            ),
+            VerifyProblem::RobinHoodOrder { table, slot } => write!(
+                fmt,
+                "table {} slot {} breaks the robin-hood ordering the file declares",
+                table, slot
+            ),
+        }
+    }
+}
+
+#[derive(Debug, Default)]
+pub struct VerifyReport {
+    pub records: usize,
+    pub slots: usize,
+    pub problems: Vec<VerifyProblem>,
+}
+
+impl VerifyReport {
+    pub fn is_ok(&self) -> bool {
+        self.problems.is_empty()
+    }
+}
+
+struct RecordInfo {
+    pos: usize,
+    value: usize,
+    hash: Hash,
+    indexed: bool,
+    empty_key: bool,
+}
+
+impl<A: CDBAccess, L: Layout> Reader<A, L> {
+    pub fn verify(&self) -> VerifyReport {
+        let mut report = VerifyReport::default();
+        let mut records = self.verify_records(&mut report);
+
+        self.verify_table_layout(&mut report);
+
+        for (tableidx, table) in self.tables().iter().enumerate() {
+            self.verify_table(tableidx, *table, &mut records, &mut report);
+        }
+
+        let padding: Vec<bool> = (0..records.len())
+            .map(|i| {
+                let r = &records[i];
+                !r.indexed
+                    && records
+                        .get(i + 1)
+                        .is_some_and(|next| Self::is_filler(r, next))
+            })
+            .collect();
+        let mut padding = padding.into_iter();
+        records.retain(|_| !padding.next().unwrap_or(false));
+        report.records = records.len();
+
+        for record in records.iter().filter(|r| !r.indexed) {
+            report
+                .problems
+                .push(VerifyProblem::UnindexedRecord { record: record.pos });
+        }
+
+        report
+    }
+
+    fn is_filler(record: &RecordInfo, next: &RecordInfo) -> bool {
+        record.empty_key && filler_shape::<L>(record.pos, next.pos, next.value)
+    }
+
+    fn verify_records(&self, report: &mut VerifyReport) -> Vec<RecordInfo> {
+        let end = self.records_end();
+        let mut pos = L::HEADER_SIZE;
+        let mut records = Vec::new();
+
+        while pos < end {
+            match self.get_key_and_value(pos) {
+                Ok((k, _, newpos)) => {
+                    records.push(RecordInfo {
+                        pos,
+                        value: pos + L::PAIR_SIZE + k.as_ref().len(),
+                        hash: self.layout().hash(k.as_ref()),
+                        indexed: false,
+                        empty_key: k.as_ref().is_empty(),
+                    });
+                    if newpos > end {
+                        report
+                            .problems
+                            .push(VerifyProblem::RecordPastEnd { record: pos, end });
+                    }
+                    pos = newpos;
+                }
+                Err(e) => {
+                    report.problems.push(VerifyProblem::Read(e));
+                    break;
+                }
+            }
+        }
+
+        records
+    }
+
+    fn verify_table_layout(&self, report: &mut VerifyReport) {
+        let records_end = self.records_end();
+        let mut order: Vec<usize> = (0..L::ENTRIES)
+            .filter(|&i| self.tables()[i].len != 0)
+            .collect();
+        order.sort_by_key(|&i| self.tables()[i].pos);
+
+        for &i in &order {
+            if self.tables()[i].pos < records_end {
+                report.problems.push(VerifyProblem::TableOverlapsRecords {
+                    table: i,
+                    pos: self.tables()[i].pos,
+                });
+            }
+        }
+
+        for pair in order.windows(2) {
+            let (prev, next) = (self.tables()[pair[0]], self.tables()[pair[1]]);
+            if prev.pos + prev.len * L::PAIR_SIZE > next.pos {
+                report.problems.push(VerifyProblem::TablesOverlap {
+                    table: pair[1],
+                    other: pair[0],
+                });
+            }
+        }
+    }
+
+    fn verify_table(
+        &self,
+        tableidx: usize,
+        table: PosLen,
+        records: &mut [RecordInfo],
+        report: &mut VerifyReport,
+    ) {
+        let mut slots = Vec::with_capacity(table.len);
+        for slot in 0..table.len {
+            match self.read_hash_pos(table.pos + slot * L::PAIR_SIZE) {
+                Ok(v) => slots.push(v),
+                Err(e) => {
+                    report.problems.push(VerifyProblem::Read(e));
+                    return;
+                }
+            }
+        }
+
+        if self.is_robinhood() {
+            let len = table.len;
+            let distance = |slot: usize| (slot + len - L::slot_of(slots[slot].0, len)) % len;
+            for slot in 0..len {
+                let prev = (slot + len - 1) % len;
+                if slots[slot].1 != 0 && slots[prev].1 != 0 && distance(slot) > distance(prev) + 1 {
+                    report.problems.push(VerifyProblem::RobinHoodOrder {
+                        table: tableidx,
+                        slot,
+                    });
+                }
+            }
+        }
+
+        for (slot, &(stored, ptr)) in slots.iter().enumerate() {
+            if ptr == 0 {
+                continue;
+            }
+            report.slots += 1;
+
+            let record = match records.binary_search_by_key(&ptr, |r| r.pos) {
+                Ok(idx) => &mut records[idx],
+                Err(_) => {
+                    report.problems.push(VerifyProblem::SlotNotRecord {
+                        table: tableidx,
+                        slot,
+                        ptr,
+                    });
+                    continue;
+                }
+            };
+
+            if stored != record.hash {
+                report.problems.push(VerifyProblem::SlotHashMismatch {
+                    table: tableidx,
+                    slot,
+                    ptr,
+                    stored,
+                    actual: record.hash,
+                });
+                continue;
+            }
+            if L::table_of(record.hash) != tableidx {
+                report.problems.push(VerifyProblem::SlotWrongTable {
+                    table: tableidx,
+                    slot,
+                    ptr,
+                });
+                continue;
+            }
+
+            let home = L::slot_of(record.hash, table.len);
+            let mut probe = home;
+            while probe != slot && slots[probe].1 != 0 {
+                probe = (probe + 1) % table.len;
+            }
+            if probe != slot {
+                report.problems.push(VerifyProblem::SlotUnreachable {
+                    table: tableidx,
+                    slot,
+                    ptr,
+                });
+                continue;
+            }
+
+            if record.indexed {
+                report.problems.push(VerifyProblem::DuplicateSlot {
+                    table: tableidx,
+                    slot,
+                    ptr,
+                });
+            }
+            record.indexed = true;
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::MemWriter;
+
+    // (table, offset of the slot, stored hash, pointer) for every used slot.
+    type Slot = (usize, usize, u32, usize);
+
+    fn build() -> Vec<u8> {
+        let mut writer = MemWriter::new(Vec::new()).unwrap();
+        for i in 0..200u32 {
+            writer.write(&i.to_le_bytes(), b"value").unwrap();
+        }
+        writer.finish_naive().unwrap()
+    }
+
+    fn slots(data: &[u8]) -> Vec<Slot> {
+        let cdb = Reader::new(data).unwrap();
+        let mut slots = Vec::new();
+        for (table, t) in cdb.header().enumerate() {
+            for (slot, res) in cdb.table_slots(table).unwrap().enumerate() {
+                let (hash, ptr) = res.unwrap();
+                if ptr != 0 {
+                    slots.push((table, t.pos() + slot * 8, hash.0, ptr));
+                }
+            }
+        }
+        slots
+    }
+
+    fn put(data: &mut [u8], at: usize, hash: u32, ptr: usize) {
+        data[at..at + 4].copy_from_slice(&hash.to_le_bytes());
+        data[at + 4..at + 8].copy_from_slice(&(ptr as u32).to_le_bytes());
+    }
+
+    fn verify(data: &[u8]) -> VerifyReport {
+        Reader::new(data).unwrap().verify()
+    }
+
+    fn empty_after(data: &[u8], at: usize) -> bool {
+        data[at + 12..at + 16] == [0; 4]
+    }
+
+    #[test]
+    fn clean() {
+        let report = verify(&build());
+        assert!(report.is_ok(), "{:?}", report.problems);
+        assert_eq!((report.records, report.slots), (200, 200));
+    }
+
+    #[test]
+    fn slot_hash_mismatch() {
+        let mut data = build();
+        let (_, at, hash, ptr) = slots(&data)[0];
+        put(&mut data, at, hash ^ 0x1_0000, ptr);
+
+        assert!(verify(&data).problems.iter().any(|p| matches!(
+            p,
+            VerifyProblem::SlotHashMismatch { ptr: p, .. } if *p == ptr
+        )));
+    }
+
+    #[test]
+    fn slot_not_record() {
+        let mut data = build();
+        let (_, at, hash, ptr) = slots(&data)[0];
+        put(&mut data, at, hash, ptr + 1);
+
+        assert!(verify(&data).problems.iter().any(|p| matches!(
+            p,
+            VerifyProblem::SlotNotRecord { ptr: p, .. } if *p == ptr + 1
+        )));
+    }
+
+    #[test]
+    fn unindexed_record() {
+        let mut data = build();
+        let slots = slots(&data);
+        let &(table, at, _, ptr) = slots
+            .iter()
+            .find(|s| slots.iter().filter(|o| o.0 == s.0).count() == 1)
+            .unwrap();
+        put(&mut data, at, 0, 0);
+
+        let report = verify(&data);
+        assert_eq!(report.problems.len(), 1, "table {}", table);
+        assert!(matches!(
+            report.problems[0],
+            VerifyProblem::UnindexedRecord { record } if record == ptr
+        ));
+    }
+
+    #[test]
+    fn slot_wrong_table() {
+        let mut data = build();
+        let slots = slots(&data);
+        let (table, _, hash, ptr) = slots[0];
+        let &(_, at, _, _) = slots
+            .iter()
+            .find(|s| s.0 != table && empty_after(&data, s.1))
+            .unwrap();
+        put(&mut data, at + 8, hash, ptr);
+
+        assert!(verify(&data).problems.iter().any(|p| matches!(
+            p,
+            VerifyProblem::SlotWrongTable { ptr: p, .. } if *p == ptr
+        )));
+    }
+
+    #[test]
+    fn duplicate_slot() {
+        let mut data = build();
+        let &(_, at, hash, ptr) = slots(&data)
+            .iter()
+            .find(|s| empty_after(&data, s.1))
+            .unwrap();
+        put(&mut data, at + 8, hash, ptr);
+
+        assert!(verify(&data).problems.iter().any(|p| matches!(
+            p,
+            VerifyProblem::DuplicateSlot { ptr: p, .. } if *p == ptr
+        )));
+    }
+
+    #[test]
+    fn table_overlaps_records() {
+        let mut data = build();
+        // Table 0 marks the end of the records, so move another one.
+        let &(table, _, _, _) = slots(&data).iter().find(|s| s.0 != 0).unwrap();
+        data[table * 8..table * 8 + 4].copy_from_slice(&2048u32.to_le_bytes());
+
+        assert!(verify(&data).problems.iter().any(|p| matches!(
+            p,
+            VerifyProblem::TableOverlapsRecords { table: t, pos: 2048 } if *t == table
+        )));
+    }
+
+    #[test]
+    fn padding_is_not_unindexed() {
+        let mut writer = MemWriter::new(Vec::new()).unwrap();
+        writer.set_alignment(32);
+        for i in 0..200u32 {
+            writer.write(&i.to_le_bytes(), &[1; 3]).unwrap();
+        }
+        let report = verify(&writer.finish().unwrap());
+        assert!(report.is_ok(), "{:?}", report.problems);
+        assert_eq!(report.records, 200);
+    }
+}
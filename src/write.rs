@@ -1,16 +1,21 @@
-use std::collections::BTreeSet;
+use alloc::collections::BTreeSet;
+use alloc::vec;
+use alloc::vec::Vec;
+use core::mem;
+
+#[cfg(feature = "std")]
 use std::io::{Seek, SeekFrom, Write};
-use std::mem;
 
+use super::layout::MAX_PAIR_SIZE;
 use super::*;
 
 #[derive(Copy, Clone, Debug)]
-struct HashPos(Hash, u32);
+struct HashPos(Hash, u64);
 
 impl HashPos {
     #[inline]
-    fn distance(self, tlen: usize, pos: usize) -> usize {
-        let startslot = self.0.slot(tlen);
+    fn distance<L: Layout>(self, tlen: usize, pos: usize) -> usize {
+        let startslot = L::slot_of(self.0, tlen);
         pos.checked_sub(startslot)
             .unwrap_or_else(|| pos + tlen - startslot)
     }
@@ -18,46 +23,440 @@ impl HashPos {
 
 const FILLFACTOR: usize = 2;
 
-pub struct Writer<T> {
+struct Index<L: Layout> {
+    layout: L,
+    align: usize,
+    tables: Vec<Vec<HashPos>>,
+    // Key hashes for a perfect hash index, kept only when asked for.
+    keys: Option<Vec<(u64, u64)>>,
+    header: L::Tables,
+}
+
+impl<L: Layout> Index<L> {
+    fn new(layout: L, perfect: bool) -> Self {
+        Index {
+            layout,
+            align: 1,
+            tables: vec![Vec::new(); L::ENTRIES],
+            keys: if perfect { Some(Vec::new()) } else { None },
+            header: L::empty_tables(),
+        }
+    }
+
+    fn push(&mut self, k: &[u8], pos: u64) {
+        let hash = self.layout.hash(k);
+        self.tables[L::table_of(hash)].push(HashPos(hash, pos));
+        if let Some(keys) = &mut self.keys {
+            keys.push((perfect::key_hash(k), pos));
+        }
+    }
+
+    fn write_perfect<W, E>(&mut self, pos: u64, mut write: W) -> Result<u64, E>
+    where
+        W: FnMut(u64, &[u8]) -> Result<(), E>,
+        E: From<WriteError>,
+    {
+        let keys = self.keys.as_mut().ok_or(WriteError::NotPerfect)?;
+        let perfect = perfect::build(keys).ok_or(WriteError::DuplicateKey)?;
+        let pairs = perfect.displacements.iter().copied().chain(
+            perfect
+                .slots
+                .iter()
+                .map(|&(hash, ptr)| (u64::from(hash.0), ptr)),
+        );
+
+        let nbuckets = perfect.displacements.len();
+        let nslots = perfect.slots.len();
+        let mut data = vec![0u8; (nbuckets + nslots) * L::PAIR_SIZE];
+        for ((a, b), out) in pairs.zip(data.chunks_exact_mut(L::PAIR_SIZE)) {
+            let (a_out, b_out) = out.split_at_mut(L::WORD_SIZE);
+            L::write_word(a, a_out);
+            L::write_word(b, b_out);
+        }
+        write(pos, &data)?;
+
+        let header = self.header.as_mut();
+        for (entry, value) in header.iter_mut().zip(&[
+            pos as usize,
+            perfect::MAGIC as usize,
+            nbuckets,
+            nslots,
+            perfect.seed as usize,
+        ]) {
+            *entry = PosLen {
+                pos: *value,
+                len: 0,
+            };
+        }
+
+        Ok(pos + data.len() as u64)
+    }
+
+    fn write_tables<F, W, E>(
+        &mut self,
+        mut pos: u64,
+        fill: F,
+        trailer: &[u8],
+        mut write: W,
+    ) -> Result<u64, E>
+    where
+        F: Fn(&[HashPos], &mut Vec<HashPos>),
+        W: FnMut(u64, &[u8]) -> Result<(), E>,
+    {
+        let mut tout = Vec::new();
+        let mut data = Vec::new();
+
+        for (i, table) in self.tables.iter().enumerate() {
+            fill(table, &mut tout);
+            self.header.as_mut()[i] = PosLen {
+                pos: pos as usize,
+                len: tout.len(),
+            };
+
+            data.clear();
+            data.resize(tout.len() * L::PAIR_SIZE, 0);
+            for (row, out) in tout.iter().zip(data.chunks_exact_mut(L::PAIR_SIZE)) {
+                let (hash, ptr) = out.split_at_mut(L::WORD_SIZE);
+                L::write_word(u64::from(row.0 .0), hash);
+                L::write_word(row.1, ptr);
+            }
+            write(pos, &data)?;
+            pos += data.len() as u64;
+        }
+
+        if !trailer.is_empty() {
+            write(pos, trailer)?;
+            pos += trailer.len() as u64;
+        }
+
+        Ok(pos)
+    }
+
+    fn header_bytes(&self) -> Vec<u8> {
+        let mut data = vec![0u8; L::HEADER_SIZE];
+        let (tables, extra) = data.split_at_mut(L::ENTRIES * L::PAIR_SIZE);
+
+        for (header, out) in self
+            .header
+            .as_ref()
+            .iter()
+            .zip(tables.chunks_exact_mut(L::PAIR_SIZE))
+        {
+            let (pos, len) = out.split_at_mut(L::WORD_SIZE);
+            L::write_word(header.pos as u64, pos);
+            L::write_word(header.len as u64, len);
+        }
+        self.layout.write_header(extra);
+
+        data
+    }
+
+    fn record_head(klen: usize, vlen: usize) -> [u8; MAX_PAIR_SIZE] {
+        let mut head = [0u8; MAX_PAIR_SIZE];
+        let (k, v) = head[..L::PAIR_SIZE].split_at_mut(L::WORD_SIZE);
+        L::write_word(klen as u64, k);
+        L::write_word(vlen as u64, v);
+        head
+    }
+
+    fn set_alignment(&mut self, align: usize) {
+        assert!(align.is_power_of_two(), "alignment must be a power of two");
+        self.align = align;
+    }
+
+    // Bytes of filler record needed before a record at `pos` so that its
+    // value starts on an aligned offset. A filler is at least a record head.
+    fn padding(&self, pos: u64, klen: usize) -> usize {
+        let align = self.align as u64;
+        let value = pos + (L::PAIR_SIZE + klen) as u64;
+        let mut pad = ((align - value % align) % align) as usize;
+        while pad != 0 && pad < L::PAIR_SIZE {
+            pad += self.align;
+        }
+        pad
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[non_exhaustive]
+pub enum WriteError {
+    CapacityExceeded { needed: usize, capacity: usize },
+    FileTooLarge,
+    DuplicateKey,
+    NotPerfect,
+}
+
+impl core::fmt::Display for WriteError {
+    fn fmt(&self, fmt: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        match self {
+            WriteError::CapacityExceeded { needed, capacity } => write!(
+                fmt,
+                "buffer of {} bytes is too small, {} bytes needed",
+                capacity, needed
+            ),
+            WriteError::FileTooLarge => {
+                write!(fmt, "CDB file would exceed the maximum size of its layout")
+            }
+            WriteError::DuplicateKey => {
+                write!(fmt, "a perfect hash index cannot hold duplicate keys")
+            }
+            WriteError::NotPerfect => {
+                write!(fmt, "writer was not created for a perfect hash index")
+            }
+        }
+    }
+}
+
+#[cfg(feature = "std")]
+impl std::error::Error for WriteError {}
+
+#[cfg(feature = "std")]
+impl std::convert::From<WriteError> for std::io::Error {
+    fn from(error: WriteError) -> Self {
+        std::io::Error::other(error)
+    }
+}
+
+pub trait Buffer {
+    type Output;
+
+    fn grow_to(&mut self, len: usize) -> Result<(), WriteError>;
+    fn write_at(&mut self, pos: usize, data: &[u8]) -> Result<(), WriteError>;
+    fn finish(self, len: usize) -> Self::Output;
+}
+
+impl Buffer for Vec<u8> {
+    type Output = Vec<u8>;
+
+    fn grow_to(&mut self, len: usize) -> Result<(), WriteError> {
+        if self.len() < len {
+            self.resize(len, 0);
+        }
+        Ok(())
+    }
+
+    fn write_at(&mut self, pos: usize, data: &[u8]) -> Result<(), WriteError> {
+        let end = pos + data.len();
+        self.grow_to(end)?;
+        self[pos..end].copy_from_slice(data);
+        Ok(())
+    }
+
+    fn finish(mut self, len: usize) -> Vec<u8> {
+        self.truncate(len);
+        self
+    }
+}
+
+impl<'a> Buffer for &'a mut [u8] {
+    type Output = &'a mut [u8];
+
+    fn grow_to(&mut self, len: usize) -> Result<(), WriteError> {
+        if len > self.len() {
+            return Err(WriteError::CapacityExceeded {
+                needed: len,
+                capacity: self.len(),
+            });
+        }
+        Ok(())
+    }
+
+    fn write_at(&mut self, pos: usize, data: &[u8]) -> Result<(), WriteError> {
+        let needed = pos + data.len();
+        let capacity = self.len();
+        self.get_mut(pos..needed)
+            .ok_or(WriteError::CapacityExceeded { needed, capacity })?
+            .copy_from_slice(data);
+        Ok(())
+    }
+
+    fn finish(self, len: usize) -> &'a mut [u8] {
+        &mut self[..len]
+    }
+}
+
+pub struct MemWriter<B, L: Layout = Classic> {
+    buf: B,
+    pos: usize,
+    index: Index<L>,
+}
+
+impl<B: Buffer> MemWriter<B> {
+    pub fn new(buf: B) -> Result<Self, WriteError> {
+        Self::with_layout(buf, Classic)
+    }
+
+    pub fn perfect(buf: B) -> Result<Self, WriteError> {
+        Self::perfect_with_layout(buf, Classic)
+    }
+}
+
+impl<B: Buffer, L: Layout> MemWriter<B, L> {
+    pub fn with_layout(buf: B, layout: L) -> Result<Self, WriteError> {
+        Self::create(buf, Index::new(layout, false))
+    }
+
+    // Also keeps a hash of every key, which `finish_perfect` needs.
+    pub fn perfect_with_layout(buf: B, layout: L) -> Result<Self, WriteError> {
+        Self::create(buf, Index::new(layout, true))
+    }
+
+    fn create(mut buf: B, index: Index<L>) -> Result<Self, WriteError> {
+        let pos = L::HEADER_SIZE;
+        buf.write_at(0, &vec![0u8; L::HEADER_SIZE])?;
+
+        Ok(MemWriter { buf, pos, index })
+    }
+
+    pub fn set_alignment(&mut self, align: usize) {
+        self.index.set_alignment(align);
+    }
+
+    pub fn write(&mut self, k: &[u8], v: &[u8]) -> Result<(), WriteError> {
+        let pad = self.index.padding(self.pos as u64, k.len());
+        let end = L::PAIR_SIZE
+            .checked_add(k.len())
+            .and_then(|l| l.checked_add(v.len()))
+            .and_then(|l| l.checked_add(pad))
+            .and_then(|l| l.checked_add(self.pos))
+            .filter(|&end| end as u64 <= L::MAX_OFFSET)
+            .ok_or(WriteError::FileTooLarge)?;
+        self.buf.grow_to(end)?;
+
+        if pad != 0 {
+            let head = Index::<L>::record_head(0, pad - L::PAIR_SIZE);
+            self.buf.write_at(self.pos, &head[..L::PAIR_SIZE])?;
+            self.buf
+                .write_at(self.pos + L::PAIR_SIZE, &vec![0u8; pad - L::PAIR_SIZE])?;
+            self.pos += pad;
+        }
+
+        let head = Index::<L>::record_head(k.len(), v.len());
+        self.buf.write_at(self.pos, &head[..L::PAIR_SIZE])?;
+        self.buf.write_at(self.pos + L::PAIR_SIZE, k)?;
+        self.buf.write_at(self.pos + L::PAIR_SIZE + k.len(), v)?;
+
+        self.index.push(k, self.pos as u64);
+        self.pos = end;
+
+        Ok(())
+    }
+
+    fn finish_generic<F>(mut self, fill: F, trailer: &[u8]) -> Result<B::Output, WriteError>
+    where
+        F: Fn(&[HashPos], &mut Vec<HashPos>),
+    {
+        let buf = &mut self.buf;
+        let end = self
+            .index
+            .write_tables(self.pos as u64, fill, trailer, |pos, data| {
+                if pos + data.len() as u64 > L::MAX_OFFSET {
+                    return Err(WriteError::FileTooLarge);
+                }
+                buf.write_at(pos as usize, data)
+            })?;
+        self.buf.write_at(0, &self.index.header_bytes())?;
+
+        Ok(self.buf.finish(end as usize))
+    }
+
+    pub fn finish(self) -> Result<B::Output, WriteError> {
+        self.finish_robinhood()
+    }
+
+    pub fn finish_naive(self) -> Result<B::Output, WriteError> {
+        self.finish_generic(fill_table_naive::<L>, &[])
+    }
+
+    pub fn finish_btree(self) -> Result<B::Output, WriteError> {
+        self.finish_generic(fill_table_btree::<L>, &[])
+    }
+
+    pub fn finish_robinhood(self) -> Result<B::Output, WriteError> {
+        self.finish_generic(fill_table_robinhood::<L>, ROBINHOOD_MAGIC)
+    }
+
+    pub fn finish_perfect(mut self) -> Result<B::Output, WriteError> {
+        let buf = &mut self.buf;
+        let end = self.index.write_perfect(self.pos as u64, |pos, data| {
+            if pos + data.len() as u64 > L::MAX_OFFSET {
+                return Err(WriteError::FileTooLarge);
+            }
+            buf.write_at(pos as usize, data)
+        })?;
+        self.buf.write_at(0, &self.index.header_bytes())?;
+
+        Ok(self.buf.finish(end as usize))
+    }
+}
+
+#[cfg(feature = "std")]
+pub struct Writer<T, L: Layout = Classic> {
     file: T,
     pos: u64,
-    tables: Vec<Vec<HashPos>>,
-    header: [PosLen; ENTRIES],
+    index: Index<L>,
 }
 
+#[cfg(feature = "std")]
 impl<T> Writer<T>
 where
     T: Write + Seek,
 {
-    pub fn new(mut file: T) -> Result<Self, std::io::Error> {
-        let pos = (ENTRIES * PAIR_SIZE) as u64;
-        let tables = vec![Vec::new(); ENTRIES];
+    pub fn new(file: T) -> Result<Self, std::io::Error> {
+        Self::with_layout(file, Classic)
+    }
+
+    pub fn perfect(file: T) -> Result<Self, std::io::Error> {
+        Self::perfect_with_layout(file, Classic)
+    }
+}
+
+#[cfg(feature = "std")]
+impl<T, L: Layout> Writer<T, L>
+where
+    T: Write + Seek,
+{
+    pub fn with_layout(file: T, layout: L) -> Result<Self, std::io::Error> {
+        Self::create(file, Index::new(layout, false))
+    }
+
+    // Also keeps a hash of every key, which `finish_perfect` needs.
+    pub fn perfect_with_layout(file: T, layout: L) -> Result<Self, std::io::Error> {
+        Self::create(file, Index::new(layout, true))
+    }
+
+    fn create(mut file: T, index: Index<L>) -> Result<Self, std::io::Error> {
+        let pos = L::HEADER_SIZE as u64;
         file.seek(SeekFrom::Start(pos))?;
 
-        Ok(Writer {
-            file,
-            pos,
-            tables,
-            header: [PosLen { pos: 0, len: 0 }; ENTRIES],
-        })
+        Ok(Writer { file, pos, index })
+    }
+
+    pub fn set_alignment(&mut self, align: usize) {
+        self.index.set_alignment(align);
     }
 
     fn write_kv(&mut self, k: &[u8], v: &[u8]) -> Result<(), std::io::Error> {
-        self.file.write_all(&(k.len() as u32).to_le_bytes())?;
-        self.file.write_all(&(v.len() as u32).to_le_bytes())?;
+        let head = Index::<L>::record_head(k.len(), v.len());
+        self.file.write_all(&head[..L::PAIR_SIZE])?;
         self.file.write_all(k)?;
         self.file.write_all(v)?;
 
-        self.pos += (PAIR_SIZE + k.len() + v.len()) as u64;
+        self.pos += (L::PAIR_SIZE + k.len() + v.len()) as u64;
 
         Ok(())
     }
 
     pub fn write(&mut self, k: &[u8], v: &[u8]) -> Result<(), std::io::Error> {
-        let hash = Hash::new(k);
-        let tableidx = hash.table();
-
-        self.tables[tableidx].push(HashPos(hash, self.pos as u32));
+        let pad = self.index.padding(self.pos, k.len());
+        let len = (pad + L::PAIR_SIZE + k.len() + v.len()) as u64;
+        if self.pos + len > L::MAX_OFFSET {
+            return Err(WriteError::FileTooLarge.into());
+        }
+        if pad != 0 {
+            self.write_kv(b"", &vec![0u8; pad - L::PAIR_SIZE])?;
+        }
+        self.index.push(k, self.pos);
 
         self.write_kv(k, v)?;
 
@@ -66,34 +465,24 @@ where
 
     fn write_header(&mut self) -> Result<(), std::io::Error> {
         self.file.seek(SeekFrom::Start(0))?;
-
-        for header in self.header.iter() {
-            self.file.write_all(&(header.pos as u32).to_le_bytes())?;
-            self.file.write_all(&(header.len as u32).to_le_bytes())?;
-        }
+        self.file.write_all(&self.index.header_bytes())?;
 
         Ok(())
     }
 
-    fn finish_generic<F>(mut self, fill: F) -> Result<T, std::io::Error>
+    fn finish_generic<F>(mut self, fill: F, trailer: &[u8]) -> Result<T, std::io::Error>
     where
         F: Fn(&[HashPos], &mut Vec<HashPos>),
     {
-        let mut tout = Vec::new();
-
-        for (i, table) in self.tables.iter().enumerate() {
-            fill(&table, &mut tout);
-            self.header[i] = PosLen {
-                pos: self.pos as usize,
-                len: tout.len(),
-            };
-            for row in &tout {
-                let hash: u32 = row.0.into();
-                self.file.write_all(&hash.to_le_bytes())?;
-                self.file.write_all(&row.1.to_le_bytes())?;
-            }
-            self.pos += (PAIR_SIZE * tout.len()) as u64;
-        }
+        let file = &mut self.file;
+        self.pos = self
+            .index
+            .write_tables(self.pos, fill, trailer, |pos, data| {
+                if pos + data.len() as u64 > L::MAX_OFFSET {
+                    return Err(WriteError::FileTooLarge.into());
+                }
+                file.write_all(data)
+            })?;
         self.write_header()?;
         self.file.flush()?;
 
@@ -105,15 +494,29 @@ where
     }
 
     pub fn finish_naive(self) -> Result<T, std::io::Error> {
-        self.finish_generic(fill_table_naive)
+        self.finish_generic(fill_table_naive::<L>, &[])
     }
 
     pub fn finish_btree(self) -> Result<T, std::io::Error> {
-        self.finish_generic(fill_table_btree)
+        self.finish_generic(fill_table_btree::<L>, &[])
     }
 
     pub fn finish_robinhood(self) -> Result<T, std::io::Error> {
-        self.finish_generic(fill_table_robinhood)
+        self.finish_generic(fill_table_robinhood::<L>, ROBINHOOD_MAGIC)
+    }
+
+    pub fn finish_perfect(mut self) -> Result<T, std::io::Error> {
+        let file = &mut self.file;
+        self.pos = self.index.write_perfect(self.pos, |pos, data| {
+            if pos + data.len() as u64 > L::MAX_OFFSET {
+                return Err(WriteError::FileTooLarge.into());
+            }
+            file.write_all(data)
+        })?;
+        self.write_header()?;
+        self.file.flush()?;
+
+        Ok(self.file)
     }
 
     pub fn into_file(self) -> T {
@@ -125,13 +528,13 @@ where
     }
 }
 
-fn fill_table_naive(input: &[HashPos], output: &mut Vec<HashPos>) {
+fn fill_table_naive<L: Layout>(input: &[HashPos], output: &mut Vec<HashPos>) {
     let tlen = input.len() * FILLFACTOR;
     output.clear();
     output.resize(tlen, HashPos(Hash(0), 0));
 
     for hp in input {
-        let (left, right) = output.split_at_mut(hp.0.slot(tlen));
+        let (left, right) = output.split_at_mut(L::slot_of(hp.0, tlen));
 
         for slot in right.iter_mut().chain(left.iter_mut()) {
             if slot.1 == 0 {
@@ -142,7 +545,7 @@ fn fill_table_naive(input: &[HashPos], output: &mut Vec<HashPos>) {
     }
 }
 
-fn fill_table_btree(input: &[HashPos], output: &mut Vec<HashPos>) {
+fn fill_table_btree<L: Layout>(input: &[HashPos], output: &mut Vec<HashPos>) {
     let mut cache = BTreeSet::new();
     let tlen = input.len() * FILLFACTOR;
     output.clear();
@@ -151,7 +554,7 @@ fn fill_table_btree(input: &[HashPos], output: &mut Vec<HashPos>) {
     cache.extend(0..tlen);
 
     for hp in input {
-        let startpos = hp.0.slot(tlen);
+        let startpos = L::slot_of(hp.0, tlen);
         let idx = *cache
             .range(startpos..)
             .chain(cache.range(0..startpos))
@@ -164,13 +567,13 @@ fn fill_table_btree(input: &[HashPos], output: &mut Vec<HashPos>) {
     }
 }
 
-fn fill_table_robinhood(input: &[HashPos], output: &mut Vec<HashPos>) {
+fn fill_table_robinhood<L: Layout>(input: &[HashPos], output: &mut Vec<HashPos>) {
     let tlen = input.len() * FILLFACTOR;
     output.clear();
     output.resize(tlen, HashPos(Hash(0), 0));
 
     for mut hp in input.iter().cloned() {
-        let startslot = hp.0.slot(tlen);
+        let startslot = L::slot_of(hp.0, tlen);
         let (left, right) = output.split_at_mut(startslot);
         let mut slotnum = startslot;
         let mut distance = 0;
@@ -179,9 +582,9 @@ fn fill_table_robinhood(input: &[HashPos], output: &mut Vec<HashPos>) {
             if slot.1 == 0 {
                 *slot = hp;
                 break;
-            } else if slot.distance(tlen, slotnum) < distance {
+            } else if slot.distance::<L>(tlen, slotnum) < distance {
                 mem::swap(slot, &mut hp);
-                distance = hp.distance(tlen, slotnum);
+                distance = hp.distance::<L>(tlen, slotnum);
             }
             distance += 1;
             slotnum = (slotnum + 1) % tlen;